@@ -0,0 +1,61 @@
+use anyhow::Result;
+use log::{info, warn};
+
+use crate::{
+    config::Config,
+    template::{CachePolicy, Template},
+};
+
+pub(crate) fn list(config: &Config, uri: String) -> Result<()> {
+    use comfy_table::Table;
+    use comfy_table::modifiers::UTF8_ROUND_CORNERS;
+    use comfy_table::presets::UTF8_FULL;
+
+    let uri = config.resolve_alias(uri);
+
+    info!("Listing templates in {uri:?}");
+
+    let template = Template::new(uri).init(CachePolicy::MaxAge(config.get_cache_max_age()))?;
+    let templates = template.find_templates()?;
+    let mut table = Table::new();
+
+    if templates.is_empty() {
+        warn!("No templates found");
+    }
+
+    let base_uri = match &template.reference {
+        Some(reference) => format!("{}@{reference}", template.uri),
+        None => template.uri.clone(),
+    };
+    let base_subdir = template.subdir.as_deref().unwrap_or("");
+
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_header(vec!["Path", "URI"]);
+
+    for path in templates {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let subdir = [base_subdir, &path]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        let uri = if subdir.is_empty() {
+            base_uri.clone()
+        } else {
+            format!("{base_uri}#{subdir}")
+        };
+        let path = if path.is_empty() {
+            ".".to_string()
+        } else {
+            path
+        };
+
+        table.add_row(vec![path, uri]);
+    }
+
+    println!("{table}");
+
+    Ok(())
+}
@@ -1,2 +1,3 @@
 pub(crate) mod alias;
+pub(crate) mod list;
 pub(crate) mod spawn;
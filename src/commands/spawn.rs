@@ -1,19 +1,67 @@
 use anyhow::Result;
+use clap::Args;
 use log::info;
 use std::{
     env::{self, set_current_dir},
     path::PathBuf,
     str::FromStr,
 };
+use tera::Value;
 
-use crate::{config::Config, processor::Processor, template::Template, writer::Writer};
+use crate::{
+    config::Config,
+    processor::{ProcessResult, Processor, actions::ActionVec, answers::Answers},
+    template::{CachePolicy, Template},
+    writer::Writer,
+};
+
+#[derive(Args, Debug)]
+pub(crate) struct SpawnArgs {
+    /// The branch, tag or commit of the template to use
+    #[arg(long = "ref", value_name = "REF")]
+    reference: Option<String>,
+    /// The directory within the repository containing the template
+    #[arg(long, value_name = "PATH")]
+    subdir: Option<String>,
+    /// Provide a value for a variable instead of prompting for it
+    #[arg(long = "var", value_name = "NAME=VALUE")]
+    vars: Vec<String>,
+    /// Read values for variables from a TOML or JSON file instead of prompting for them
+    #[arg(long, value_name = "FILE")]
+    answers: Option<PathBuf>,
+    /// Show what would be written without touching the filesystem
+    #[arg(long)]
+    dry_run: bool,
+    /// Refresh the cached clone of the template before using it
+    #[arg(long, conflicts_with = "offline")]
+    refresh: bool,
+    /// Only use the cached clone of the template
+    #[arg(long)]
+    offline: bool,
+}
+
+impl SpawnArgs {
+    fn cache_policy(&self, config: &Config) -> CachePolicy {
+        if self.offline {
+            CachePolicy::Offline
+        } else if self.refresh {
+            CachePolicy::Refresh
+        } else {
+            CachePolicy::MaxAge(config.get_cache_max_age())
+        }
+    }
+}
 
-pub(crate) fn spawn(config: &Config, uri: String) -> Result<()> {
+pub(crate) fn spawn(config: &Config, uri: String, args: &SpawnArgs) -> Result<()> {
     let uri = config.resolve_alias(uri);
+    let mut answers = Answers::try_from_args(&args.vars, args.answers.as_deref())?;
 
     info!("Using template {uri:?}");
 
-    let template = Template::new(uri).init()?;
+    let template = Template::new(uri)
+        .with_reference(args.reference.clone())
+        .with_subdir(args.subdir.clone())
+        .init(args.cache_policy(config))?;
     let plugins = template.get_plugins()?;
     let template_info = template.get_info()?;
     let template_info = plugins.info(template_info.map(String::as_str))?;
@@ -23,6 +71,10 @@ pub(crate) fn spawn(config: &Config, uri: String) -> Result<()> {
     }
 
     cliclack::intro(console::style(" SPWN ").on_cyan().black().bold())?;
+    cliclack::log::remark(match template.get_commit()? {
+        Some(commit) => format!("Using {} at {commit}", template.uri),
+        None => format!("Using {}", template.uri),
+    })?;
 
     let cwd = env::current_dir()?;
     let cwd = plugins.cwd(&cwd.to_string_lossy())?;
@@ -32,16 +84,92 @@ pub(crate) fn spawn(config: &Config, uri: String) -> Result<()> {
 
     info!("The current directory is {cwd:?}");
 
-    let processor = Processor::from_template(&template);
-    let process_result = processor.process(&cwd)?;
+    let process_result = loop {
+        let interactive = answers.is_interactive();
+        let processor = Processor::from_template(&template).with_answers(answers);
+        let process_result = processor.process(&cwd)?;
+
+        if !interactive {
+            break process_result;
+        }
+
+        match review(&template, &process_result)? {
+            Some(identifier) => {
+                // Prompt again for the chosen variable, keeping the other
+                // answers.
+                answers = Answers::prefilled(
+                    process_result
+                        .answers
+                        .into_iter()
+                        .filter(|(answered, _)| *answered != identifier),
+                );
+            }
+            None => break process_result,
+        }
+    };
 
     process_result.log()?;
 
     let writer = Writer::from_process_result(&process_result);
 
-    writer.write()?;
+    if args.dry_run {
+        writer.preview()?;
+
+        cliclack::outro("Dry run, no files were written")?;
+
+        return Ok(());
+    }
+
+    let report = writer.write()?;
+
+    if let Some(message) = plugins.after_write(
+        &report.created,
+        &report.replaced,
+        &report.skipped,
+        &process_result.context,
+    )? {
+        cliclack::log::info(message)?;
+    }
 
     cliclack::outro("Done!")?;
 
     Ok(())
 }
+
+/// Show the collected answers and let the user pick one to answer again,
+/// returning `None` when they're confirmed.
+fn review<'a>(
+    template: &'a Template<'a>,
+    process_result: &ProcessResult,
+) -> Result<Option<String>> {
+    if process_result.answers.is_empty() {
+        return Ok(None);
+    }
+
+    let config = template.get_config()?;
+    let answers = process_result
+        .answers
+        .iter()
+        .map(|(identifier, value)| {
+            let value = match value {
+                _ if config.is_secret(identifier) => "********".to_string(),
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+
+            format!("{identifier}: {value}")
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let summary = process_result.actions.get_grouped_actions().summary();
+
+    cliclack::note("Review", format!("{answers}\n\n{summary}"))?;
+
+    let mut prompt = cliclack::select("Continue with these values?").item(None, "Continue", "");
+
+    for (identifier, _) in &process_result.answers {
+        prompt = prompt.item(Some(identifier.clone()), format!("Change {identifier}"), "");
+    }
+
+    Ok(prompt.interact()?)
+}
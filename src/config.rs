@@ -2,13 +2,17 @@ use anyhow::{Error, Result};
 use directories::ProjectDirs;
 use log::info;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
 const IGNORE_GLOBAL_FILENAME: &str = ".spwnignore_global";
 
 #[derive(Default, Deserialize, Serialize)]
 pub struct Config {
+    #[serde(default)]
     aliases: HashMap<String, String>,
+    /// The number of seconds after which cached templates are refreshed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cache_max_age: Option<u64>,
 }
 
 impl Config {
@@ -48,6 +52,10 @@ impl Config {
         }
     }
 
+    pub fn get_cache_max_age(&self) -> Option<Duration> {
+        self.cache_max_age.map(Duration::from_secs)
+    }
+
     pub fn get_aliases(&self) -> &HashMap<String, String> {
         &self.aliases
     }
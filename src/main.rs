@@ -12,7 +12,7 @@ use clap_verbosity::Verbosity;
 use config::Config;
 use log::error;
 
-use commands::{alias, spawn};
+use commands::{alias, list, spawn};
 
 /// Create files and folders from templates
 #[derive(Parser, Debug)]
@@ -24,6 +24,8 @@ struct Cli {
     #[arg()]
     uri: Option<String>,
     #[command(flatten)]
+    spawn: spawn::SpawnArgs,
+    #[command(flatten)]
     verbose: Verbosity,
 }
 
@@ -34,6 +36,12 @@ enum Commands {
         #[command(subcommand)]
         command: AliasCommands,
     },
+    /// List the templates in a repository
+    #[command(visible_alias = "ls")]
+    List {
+        /// Location of the repository
+        uri: String,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -68,7 +76,7 @@ fn main() {
         let mut config = Config::read()?;
 
         match (cli.uri, cli.command) {
-            (Some(uri), None) => spawn::spawn(&config, uri),
+            (Some(uri), None) => spawn::spawn(&config, uri, &cli.spawn),
             (None, Some(Commands::Alias { command })) => match command {
                 AliasCommands::Add { name, uri } => alias::add(&mut config, name, uri),
                 AliasCommands::Remove { name } => alias::remove(&mut config, &name),
@@ -77,6 +85,7 @@ fn main() {
                     Ok(())
                 }
             },
+            (None, Some(Commands::List { uri })) => list::list(&config, uri),
             _ => Err(Error::msg(
                 "Provide either a command or location of a template",
             )),
@@ -1,72 +1,351 @@
 pub(crate) mod actions;
+pub(crate) mod answers;
+mod identifiers;
 mod prompt;
 mod tera_extensions;
 
 use actions::{Action, ActionVec, Write};
-use anyhow::Result;
+use answers::Answers;
+use anyhow::{Error, Result};
 use log::{info, warn};
-use std::path::Path;
-use tera::{Context, Tera};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use tera::{Context, Tera, Value};
 
-use crate::template::Template;
+use crate::template::{Template, config::VarKind};
 
 const FILENAME_TEMPLATE_NAME: &str = "__filename_template";
+const STRING_TEMPLATE_NAME: &str = "__string_template";
 
 pub(crate) struct Processor<'a> {
     template: &'a Template<'a>,
+    answers: Answers,
 }
 
 impl<'a> Processor<'a> {
     pub(crate) fn from_template(template: &'a Template<'a>) -> Self {
-        Self { template }
+        Self {
+            template,
+            answers: Answers::interactive(),
+        }
+    }
+
+    pub(crate) fn with_answers(mut self, answers: Answers) -> Self {
+        self.answers = answers;
+
+        self
     }
 
     pub(crate) fn process(&self, cwd: &Path) -> Result<ProcessResult> {
         let plugins = self.template.get_plugins()?;
-        let cache_dir = self.template.cache_dir()?;
-        let ignore = self.get_ignore()?;
-        let mut tera = tera_extensions::extend(Tera::default());
-        let mut context = plugins.context(Context::new())?;
+        let (entries, mut identifiers) = self.discover()?;
+        let file_conditions = self.get_file_conditions()?;
+
+        // The conditions of file rules are always evaluated, so their values
+        // are collected along with the others.
+        for condition in &file_conditions {
+            for identifier in &condition.identifiers {
+                if !identifiers.contains(identifier) {
+                    identifiers.push(identifier.clone());
+                }
+            }
+        }
+
+        let mut state = State {
+            tera: tera_extensions::extend(Tera::default()),
+            context: plugins.context(Context::new())?,
+            missing: Vec::new(),
+            skipped: HashSet::new(),
+            pending: Vec::new(),
+            answers: Vec::new(),
+            entries,
+            file_conditions,
+            included: HashMap::new(),
+            allowed: HashMap::new(),
+        };
+
+        info!("Initial context {:?}", state.context);
+
+        plugins.set_context(&state.context)?;
+
+        for identifier in self.order_identifiers(identifiers)? {
+            let in_condition = state
+                .file_conditions
+                .iter()
+                .any(|condition| condition.identifiers.contains(&identifier));
+
+            if in_condition || self.is_used(&mut state, &identifier)? {
+                self.collect_var(&mut state, &identifier)?;
+            }
+        }
+
+        // Decide on every file before checking for missing values, so none
+        // of them prompt later on.
+        for index in 0..state.entries.len() {
+            self.is_included(&mut state, index)?;
+        }
+
+        // Values that are missing can still be provided by the plugins.
+        for identifier in &state.missing {
+            state.context.remove(identifier);
+        }
+
+        state.context = plugins.finalize_context(std::mem::take(&mut state.context))?;
+        plugins.set_context(&state.context)?;
+        state
+            .missing
+            .retain(|identifier| !state.context.contains_key(identifier));
 
-        info!("Initial context {context:?}");
+        if !state.missing.is_empty() {
+            let identifiers = state
+                .missing
+                .iter()
+                .map(|identifier| format!("- {identifier}"))
+                .collect::<Vec<_>>()
+                .join("\n");
 
+            return Err(Error::msg(format!(
+                "Missing values for the following variables:\n{identifiers}"
+            )));
+        }
+
+        let actions = self.get_actions(&mut state, cwd)?;
+
+        Ok(ProcessResult {
+            tera: state.tera,
+            context: state.context,
+            actions,
+            answers: state.answers,
+        })
+    }
+
+    fn get_actions(&self, state: &mut State, cwd: &Path) -> Result<Vec<Action>> {
         let mut actions: Vec<Action> = Vec::new();
 
-        for path in walkdir::WalkDir::new(&cache_dir)
+        for index in 0..state.entries.len() {
+            let entry = &state.entries[index];
+
+            if entry.path.is_dir() {
+                continue;
+            }
+
+            let path = entry.path.clone();
+
+            if !self.is_included(state, index)? || !self.is_allowed(state, index)? {
+                info!("Excluding {path:?}");
+
+                continue;
+            }
+
+            let name = state
+                .tera
+                .render_str(&state.entries[index].name, &state.context)?;
+
+            if has_empty_segment(&name) {
+                info!("Excluding {path:?} because its name renders to {name:?}");
+
+                continue;
+            }
+
+            state.tera.add_template_file(&path, Some(&name))?;
+
+            let target = cwd.join(std::path::Path::new(&name));
+            let write = Write { name, target };
+
+            actions.push(write.into());
+        }
+
+        Ok(actions)
+    }
+
+    /// Walk the template to find its files and the identifiers used in their
+    /// names and contents, in the order they first appear.
+    fn discover(&self) -> Result<(Vec<Entry>, Vec<String>)> {
+        let root_dir = self.template.root_dir()?;
+        let ignore = self.get_ignore()?;
+        let mut entries = Vec::new();
+        let mut identifiers: Vec<String> = Vec::new();
+
+        for path in walkdir::WalkDir::new(&root_dir)
             .min_depth(1)
             .into_iter()
             .filter_map(Result::ok)
             .filter(|path| {
-                pathdiff::diff_paths(path.path(), &cache_dir).is_some_and(|p| !ignore.is_match(&p))
+                pathdiff::diff_paths(path.path(), &root_dir).is_some_and(|p| !ignore.is_match(&p))
             })
         {
             let path = path.path();
-            let Some(rel_path) = pathdiff::diff_paths(path, &cache_dir) else {
+            let Some(rel_path) = pathdiff::diff_paths(path, &root_dir) else {
                 continue;
             };
             let Some(name) = rel_path.to_str() else {
                 continue;
             };
-            let name = self.process_filename(&mut tera, &mut context, name)?;
+            let mut templates = vec![tera::Template::new(FILENAME_TEMPLATE_NAME, None, name)?];
 
-            if path.is_dir() {
-                continue;
+            if path.is_file() {
+                let contents = std::fs::read_to_string(path)?;
+
+                templates.push(tera::Template::new(name, None, &contents)?);
             }
 
-            tera.add_template_file(path, Some(&name))?;
-            self.collect_vars(tera.get_template(&name)?, &mut context)?;
+            let mut entry_identifiers = Vec::new();
 
-            let target = cwd.join(std::path::Path::new(&name));
-            let write = Write { name, target };
+            for template in &templates {
+                for identifier in identifiers::find_identifiers(&template.ast) {
+                    if !identifiers.contains(&identifier) {
+                        identifiers.push(identifier.clone());
+                    }
 
-            actions.push(write.into());
+                    if !entry_identifiers.contains(&identifier) {
+                        entry_identifiers.push(identifier);
+                    }
+                }
+            }
+
+            entries.push(Entry {
+                path: path.to_path_buf(),
+                name: name.to_string(),
+                identifiers: entry_identifiers,
+            });
         }
 
-        Ok(ProcessResult {
-            tera,
-            context,
-            actions,
-        })
+        Ok((entries, identifiers))
+    }
+
+    /// Whether an identifier is used by any file or directory that is
+    /// included.
+    fn is_used(&self, state: &mut State, identifier: &str) -> Result<bool> {
+        for index in 0..state.entries.len() {
+            if state.entries[index]
+                .identifiers
+                .iter()
+                .any(|used| used == identifier)
+                && self.is_included(state, index)?
+            {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Whether a file or directory is included, which requires its parent to
+    /// be included, the conditions of the file rules matching it to be met
+    /// and none of the segments of its name to render to an empty string.
+    fn is_included(&self, state: &mut State, index: usize) -> Result<bool> {
+        let name = state.entries[index].name.clone();
+
+        if let Some(included) = state.included.get(&name) {
+            return Ok(*included);
+        }
+
+        let parent = Path::new(&name).parent().and_then(Path::to_str);
+        let parent =
+            parent.and_then(|parent| state.entries.iter().position(|entry| entry.name == parent));
+        let mut included = match parent {
+            Some(parent) => self.is_included(state, parent)?,
+            None => true,
+        };
+
+        for condition_index in 0..state.file_conditions.len() {
+            if !included {
+                break;
+            }
+
+            let FileCondition { matcher, when, .. } = &state.file_conditions[condition_index];
+
+            if matcher.is_match(&name) {
+                let when = when.clone();
+
+                included = self.evaluate_condition(state, &when)?;
+            }
+        }
+
+        if included {
+            let template = tera::Template::new(STRING_TEMPLATE_NAME, None, &name)?;
+            let identifiers = identifiers::find_identifiers(&template.ast);
+
+            for identifier in &identifiers {
+                self.collect_var(state, identifier)?;
+            }
+
+            // Names using values that are left for the plugins are only
+            // checked once those are final.
+            if identifiers
+                .iter()
+                .all(|identifier| state.context.contains_key(identifier))
+            {
+                let rendered = state.tera.render_str(&name, &state.context)?;
+
+                included = !has_empty_segment(&rendered);
+            }
+        }
+
+        state.included.insert(name, included);
+
+        Ok(included)
+    }
+
+    /// Whether the `include-file?` plugin allows a file or directory and its
+    /// parents, which is only asked once all values are final.
+    fn is_allowed(&self, state: &mut State, index: usize) -> Result<bool> {
+        let name = state.entries[index].name.clone();
+
+        if let Some(allowed) = state.allowed.get(&name) {
+            return Ok(*allowed);
+        }
+
+        let parent = Path::new(&name).parent().and_then(Path::to_str);
+        let parent =
+            parent.and_then(|parent| state.entries.iter().position(|entry| entry.name == parent));
+        let allowed = match parent {
+            Some(parent) if !self.is_allowed(state, parent)? => false,
+            _ => self.template.get_plugins()?.include_file(&name)?,
+        };
+
+        state.allowed.insert(name, allowed);
+
+        Ok(allowed)
+    }
+
+    fn get_file_conditions(&self) -> Result<Vec<FileCondition>> {
+        self.template
+            .get_config()?
+            .get_file_rules()
+            .iter()
+            .map(|rule| {
+                let source = condition_source(&rule.when);
+                let template = tera::Template::new(STRING_TEMPLATE_NAME, None, &source)?;
+
+                Ok(FileCondition {
+                    matcher: globset::Glob::new(&rule.path)?.compile_matcher(),
+                    when: rule.when.clone(),
+                    identifiers: identifiers::find_identifiers(&template.ast),
+                })
+            })
+            .collect()
+    }
+
+    /// Order identifiers to prompt for the variables declared in the template
+    /// config first, followed by the ones that aren't declared.
+    fn order_identifiers(&self, identifiers: Vec<String>) -> Result<Vec<String>> {
+        let config = self.template.get_config()?;
+        let (mut declared, undeclared): (Vec<_>, Vec<_>) = identifiers
+            .into_iter()
+            .partition(|identifier| config.get_declared_var(identifier).is_some());
+        let declared_identifiers = config.get_declared_identifiers();
+
+        declared.sort_by_key(|identifier| {
+            declared_identifiers
+                .iter()
+                .position(|declared| declared == identifier)
+        });
+        declared.extend(undeclared);
+
+        Ok(declared)
     }
 
     fn get_ignore(&self) -> Result<globset::GlobSet> {
@@ -95,55 +374,237 @@ impl<'a> Processor<'a> {
         Ok(builder.build()?)
     }
 
-    fn process_filename(
-        &self,
-        tera: &mut Tera,
-        context: &mut Context,
-        name: &str,
-    ) -> Result<String> {
-        tera.add_raw_template(FILENAME_TEMPLATE_NAME, name)?;
-        self.collect_vars(tera.get_template(FILENAME_TEMPLATE_NAME)?, context)?;
-        let result = tera.render(FILENAME_TEMPLATE_NAME, context);
-        tera.templates.remove(FILENAME_TEMPLATE_NAME);
+    fn collect_var(&self, state: &mut State, identifier: &str) -> Result<()> {
+        if state.context.contains_key(identifier)
+            || state.skipped.contains(identifier)
+            || state.missing.iter().any(|missing| missing == identifier)
+        {
+            return Ok(());
+        }
+
+        if state.pending.iter().any(|pending| pending == identifier) {
+            return Err(Error::msg(format!(
+                "The variable {identifier:?} depends on itself"
+            )));
+        }
+
+        state.pending.push(identifier.to_string());
+        let resolved = self.resolve_var(state, identifier);
+        state.pending.pop();
+
+        let resolved = resolved?;
+
+        if let Resolved::Answer(value) = &resolved {
+            state.answers.push((identifier.to_string(), value.clone()));
+        }
+
+        match resolved {
+            Resolved::Value(value) | Resolved::Answer(value) => {
+                let value = self.convert_value(identifier, value)?;
+
+                if self.template.get_config()?.is_secret(identifier) {
+                    info!("Collected secret value for {identifier:?}");
+                } else {
+                    info!("Collected value {value} for {identifier:?}");
+                }
+
+                state.context.insert(identifier, &value);
+            }
+            Resolved::Skipped(default) => {
+                info!("Skipping {identifier:?} because its condition isn't met");
+
+                state.skipped.insert(identifier.to_string());
 
-        Ok(result?)
+                if let Some(default) = default {
+                    state.context.insert(identifier, &default);
+                }
+            }
+            Resolved::Missing => {
+                // Continue with an empty value so every missing
+                // identifier can be reported at once.
+                state.missing.push(identifier.to_string());
+                state.context.insert(identifier, "");
+            }
+            Resolved::Deferred => {
+                info!("Leaving {identifier:?} to be provided by the plugins");
+
+                state.missing.push(identifier.to_string());
+            }
+        }
+
+        self.template.get_plugins()?.set_context(&state.context)
     }
 
-    fn collect_vars(
-        &self,
-        tera_template: &tera::Template,
-        context: &mut tera::Context,
-    ) -> Result<()> {
-        use tera::ast::{ExprVal, Node};
-        let ast = &tera_template.ast;
+    fn resolve_var(&self, state: &mut State, identifier: &str) -> Result<Resolved> {
+        let config = self.template.get_config()?;
+        let declared = config.get_declared_var(identifier);
 
-        for node in ast {
-            let Node::VariableBlock(_, expr) = node else {
-                continue;
-            };
-            let ExprVal::Ident(identifier) = &expr.val else {
-                continue;
-            };
+        if let Some(when) = declared.and_then(|var| var.when.as_deref())
+            && !self.evaluate_condition(state, when)?
+        {
+            let default = config
+                .get_var(identifier, |source| self.render(state, source))?
+                .default_value();
 
-            if context.contains_key(identifier) {
-                continue;
+            return Ok(Resolved::Skipped(default));
+        }
+
+        if let Some(VarKind::Computed { value }) = declared.map(|var| &var.kind) {
+            let rendered = value
+                .as_deref()
+                .map(|value| self.render(state, value))
+                .transpose()?;
+            let computed = self
+                .template
+                .get_plugins()?
+                .default(identifier, rendered.as_deref())?;
+
+            return Ok(match (computed, rendered) {
+                (Some(value), _) => Resolved::Value(value.into()),
+                // Without a value it's up to the plugins to provide one.
+                (None, None) => Resolved::Deferred,
+                (None, Some(_)) => Resolved::Value("".into()),
+            });
+        }
+
+        let var = config.get_var(identifier, |source| self.render(state, source))?;
+        let value = match self.answers.get(identifier) {
+            Some(value) => prompt::answer(self.template, var, value)?,
+            None if self.answers.is_interactive() => prompt::prompt(self.template, var)?,
+            // Fall back to the default when not prompting, which can depend
+            // on the values provided for other variables and is checked like
+            // a provided value.
+            None => {
+                return Ok(match var.default_value() {
+                    Some(value) => Resolved::Value(prompt::answer(self.template, var, &value)?),
+                    None => Resolved::Missing,
+                });
+            }
+        };
+
+        Ok(Resolved::Answer(value))
+    }
+
+    /// Convert a value to the type declared for its variable, if any.
+    fn convert_value(&self, identifier: &str, value: Value) -> Result<Value> {
+        let config = self.template.get_config()?;
+        let Some(value_type) = config
+            .get_declared_var(identifier)
+            .and_then(|var| var.value_type)
+        else {
+            return Ok(value);
+        };
+
+        value_type.convert(&value).map_err(|message| {
+            if config.is_secret(identifier) {
+                Error::msg(format!("Invalid value for '{identifier}': {message}"))
+            } else {
+                Error::msg(format!(
+                    "Invalid value {value} for '{identifier}': {message}"
+                ))
             }
+        })
+    }
 
-            let value = prompt::prompt(self.template, identifier)?;
+    /// Evaluate a condition, which is either a Tera expression like
+    /// `use_db and db != "none"` or a template like `{{ use_db }}` that renders
+    /// to something other than an empty string or `false`.
+    fn evaluate_condition(&self, state: &mut State, condition: &str) -> Result<bool> {
+        let result = self.render(state, &condition_source(condition))?;
+        let result = result.trim();
 
-            info!("Collected value {value:?} for {identifier:?}");
+        Ok(!result.is_empty() && result != "false")
+    }
+
+    /// Render a string as a template, collecting values for the variables it
+    /// uses first.
+    fn render(&self, state: &mut State, source: &str) -> Result<String> {
+        let template = tera::Template::new(STRING_TEMPLATE_NAME, None, source)?;
 
-            context.insert(identifier, &value);
+        for identifier in &identifiers::find_identifiers(&template.ast) {
+            self.collect_var(state, identifier)?;
         }
 
-        Ok(())
+        Ok(state.tera.render_str(source, &state.context)?)
+    }
+}
+
+/// Get the template for a condition, wrapping expressions in an `if` tag.
+fn condition_source(condition: &str) -> String {
+    if condition.contains("{{") || condition.contains("{%") {
+        condition.to_string()
+    } else {
+        format!("{{% if {condition} %}}true{{% endif %}}")
     }
 }
 
+/// Whether a rendered name has a segment that's empty, which excludes the
+/// file like `{% if use_ci %}.github{% endif %}/ci.yml`.
+fn has_empty_segment(name: &str) -> bool {
+    name.split(['/', std::path::MAIN_SEPARATOR])
+        .any(str::is_empty)
+}
+
+/// The outcome of resolving the value for a variable.
+enum Resolved {
+    /// A value that was provided or prompted for.
+    Answer(Value),
+    Value(Value),
+    /// The variable's condition isn't met, so its default is used if any.
+    Skipped(Option<Value>),
+    /// No value was provided and prompting is disabled.
+    Missing,
+    /// A computed variable without a value, which is left for the
+    /// `finalize-context` plugin function to provide.
+    Deferred,
+}
+
+/// A file or directory in the template.
+struct Entry {
+    path: PathBuf,
+    /// The path relative to the template, which can contain Tera syntax.
+    name: String,
+    /// The identifiers used in the name and contents.
+    identifiers: Vec<String>,
+}
+
+/// A condition for including the files matching a glob.
+struct FileCondition {
+    matcher: globset::GlobMatcher,
+    when: String,
+    /// The identifiers used in the condition.
+    identifiers: Vec<String>,
+}
+
+/// The state while processing a template.
+struct State {
+    tera: Tera,
+    context: Context,
+    /// Identifiers without a value when not prompting, or that are left for
+    /// the plugins to provide.
+    missing: Vec<String>,
+    /// Identifiers that weren't prompted for because of their condition.
+    skipped: HashSet<String>,
+    /// Identifiers whose conditions are being evaluated.
+    pending: Vec<String>,
+    /// Values that were provided or prompted for, in the order they were
+    /// collected.
+    answers: Vec<(String, Value)>,
+    entries: Vec<Entry>,
+    file_conditions: Vec<FileCondition>,
+    /// Whether files and directories are included by their names.
+    included: HashMap<String, bool>,
+    /// Whether the plugins allow files and directories by their names.
+    allowed: HashMap<String, bool>,
+}
+
 pub(crate) struct ProcessResult {
     pub(crate) tera: Tera,
     pub(crate) context: Context,
     pub(crate) actions: Vec<Action>,
+    /// Values that were provided or prompted for, in the order they were
+    /// collected.
+    pub(crate) answers: Vec<(String, Value)>,
 }
 
 impl ProcessResult {
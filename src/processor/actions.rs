@@ -48,6 +48,23 @@ pub(crate) struct GroupedActions<'a> {
 }
 
 impl GroupedActions<'_> {
+    /// Describe the number of files to create and replace.
+    pub(crate) fn summary(&self) -> String {
+        let count = |n: usize| {
+            if n == 1 {
+                "1 file".to_string()
+            } else {
+                format!("{n} files")
+            }
+        };
+
+        format!(
+            "Create {}, replace {}",
+            count(self.create.len()),
+            count(self.replace.len())
+        )
+    }
+
     pub(crate) fn log(&self) -> std::io::Result<()> {
         if !self.create.is_empty() {
             let files = self
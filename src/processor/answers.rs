@@ -0,0 +1,78 @@
+use anyhow::{Error, Result};
+use log::info;
+use std::{collections::HashMap, path::Path};
+use tera::Value;
+
+/// Values for variables that are provided up front instead of prompted for.
+pub(crate) struct Answers {
+    values: HashMap<String, Value>,
+    /// Whether to prompt for values that weren't provided.
+    interactive: bool,
+}
+
+impl Answers {
+    pub(crate) fn interactive() -> Self {
+        Self {
+            values: HashMap::new(),
+            interactive: true,
+        }
+    }
+
+    /// Create answers that prompt for any value not in `values`.
+    pub(crate) fn prefilled(values: impl IntoIterator<Item = (String, Value)>) -> Self {
+        Self {
+            values: values.into_iter().collect(),
+            interactive: true,
+        }
+    }
+
+    /// Create answers from `NAME=VALUE` pairs and an optional answers file.
+    ///
+    /// Prompting is disabled as soon as any value is provided. Values from
+    /// `vars` take precedence over values from the file.
+    pub(crate) fn try_from_args(vars: &[String], file: Option<&Path>) -> Result<Self> {
+        if vars.is_empty() && file.is_none() {
+            return Ok(Self::interactive());
+        }
+
+        let mut values = match file {
+            Some(file) => read_file(file)?,
+            None => HashMap::new(),
+        };
+
+        for var in vars {
+            let Some((name, value)) = var.split_once('=') else {
+                return Err(Error::msg(format!(
+                    "Expected a variable as NAME=VALUE but got {var:?}"
+                )));
+            };
+
+            values.insert(name.trim().to_string(), Value::String(value.to_string()));
+        }
+
+        Ok(Self {
+            values,
+            interactive: false,
+        })
+    }
+
+    pub(crate) fn is_interactive(&self) -> bool {
+        self.interactive
+    }
+
+    pub(crate) fn get(&self, identifier: &str) -> Option<&Value> {
+        self.values.get(identifier)
+    }
+}
+
+fn read_file(path: &Path) -> Result<HashMap<String, Value>> {
+    info!("Using answers file {path:?}");
+
+    let data = std::fs::read_to_string(path)?;
+    let values = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str(&data)?,
+        _ => toml::from_str(&data)?,
+    };
+
+    Ok(values)
+}
@@ -0,0 +1,257 @@
+use std::collections::HashSet;
+use tera::ast::{Expr, ExprVal, FunctionCall, Node};
+
+/// Names that are provided by Tera itself while rendering.
+const BUILTIN_NAMES: &[&str] = &["__tera_context", "loop"];
+
+/// Find the identifiers in a template that need a value from the context.
+///
+/// Identifiers are returned in the order they first appear, ignoring names that
+/// are defined by the template itself like loop variables, macro arguments and
+/// variables assigned with `set`.
+pub(super) fn find_identifiers(ast: &[Node]) -> Vec<String> {
+    let mut finder = Finder {
+        identifiers: Vec::new(),
+        scopes: vec![HashSet::new()],
+    };
+
+    finder.visit_nodes(ast);
+
+    finder.identifiers
+}
+
+struct Finder {
+    identifiers: Vec<String>,
+    /// Names defined by the template, with the innermost scope last.
+    scopes: Vec<HashSet<String>>,
+}
+
+impl Finder {
+    fn visit_nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.visit_node(node);
+        }
+    }
+
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::VariableBlock(_, expr) => self.visit_expr(expr),
+            Node::MacroDefinition(_, definition, _) => {
+                for default in definition.args.values().flatten() {
+                    self.visit_expr(default);
+                }
+
+                self.with_scope(definition.args.keys().cloned(), |finder| {
+                    finder.visit_nodes(&definition.body);
+                });
+            }
+            Node::Set(_, set) => {
+                self.visit_expr(&set.value);
+
+                let scope = if set.global {
+                    self.scopes.first_mut()
+                } else {
+                    self.scopes.last_mut()
+                };
+
+                if let Some(scope) = scope {
+                    scope.insert(set.key.clone());
+                }
+            }
+            Node::FilterSection(_, section, _) => {
+                self.visit_function_call(&section.filter);
+                self.visit_nodes(&section.body);
+            }
+            Node::Block(_, block, _) => self.visit_nodes(&block.body),
+            Node::Forloop(_, forloop, _) => {
+                self.visit_expr(&forloop.container);
+
+                let names = forloop.key.iter().chain([&forloop.value]).cloned();
+
+                self.with_scope(names, |finder| finder.visit_nodes(&forloop.body));
+
+                if let Some(empty_body) = &forloop.empty_body {
+                    self.visit_nodes(empty_body);
+                }
+            }
+            Node::If(if_node, _) => {
+                for (_, condition, body) in &if_node.conditions {
+                    self.visit_expr(condition);
+                    self.visit_nodes(body);
+                }
+
+                if let Some((_, body)) = &if_node.otherwise {
+                    self.visit_nodes(body);
+                }
+            }
+            Node::Super
+            | Node::Text(_)
+            | Node::Extends(..)
+            | Node::Include(..)
+            | Node::ImportMacro(..)
+            | Node::Raw(..)
+            | Node::Break(_)
+            | Node::Continue(_)
+            | Node::Comment(..) => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.visit_expr_val(&expr.val);
+
+        for filter in &expr.filters {
+            self.visit_function_call(filter);
+        }
+    }
+
+    fn visit_expr_val(&mut self, val: &ExprVal) {
+        match val {
+            ExprVal::Ident(identifier) => self.add(identifier),
+            ExprVal::Math(math) => {
+                self.visit_expr(&math.lhs);
+                self.visit_expr(&math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                self.visit_expr(&logic.lhs);
+                self.visit_expr(&logic.rhs);
+            }
+            ExprVal::Test(test) => {
+                // Testing whether a variable is defined shouldn't require a
+                // value for it.
+                if !matches!(test.name.as_str(), "defined" | "undefined") {
+                    self.add(&test.ident);
+                }
+
+                for arg in &test.args {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprVal::MacroCall(call) => {
+                for arg in call.args.values() {
+                    self.visit_expr(arg);
+                }
+            }
+            ExprVal::FunctionCall(call) => self.visit_function_call(call),
+            ExprVal::Array(items) => {
+                for item in items {
+                    self.visit_expr(item);
+                }
+            }
+            ExprVal::StringConcat(concat) => {
+                for value in &concat.values {
+                    self.visit_expr_val(value);
+                }
+            }
+            ExprVal::In(in_expr) => {
+                self.visit_expr(&in_expr.lhs);
+                self.visit_expr(&in_expr.rhs);
+            }
+            ExprVal::String(_) | ExprVal::Int(_) | ExprVal::Float(_) | ExprVal::Bool(_) => {}
+        }
+    }
+
+    fn visit_function_call(&mut self, call: &FunctionCall) {
+        for arg in call.args.values() {
+            self.visit_expr(arg);
+        }
+    }
+
+    fn with_scope(
+        &mut self,
+        names: impl IntoIterator<Item = String>,
+        visit: impl FnOnce(&mut Self),
+    ) {
+        self.scopes.push(names.into_iter().collect());
+        visit(self);
+        self.scopes.pop();
+    }
+
+    fn add(&mut self, identifier: &str) {
+        // Only the root of an identifier like `user.name` or `items[0]` is
+        // needed in the context.
+        let identifier = identifier.split(['.', '[']).next().unwrap_or(identifier);
+
+        if BUILTIN_NAMES.contains(&identifier)
+            || self.scopes.iter().any(|scope| scope.contains(identifier))
+            || self.identifiers.iter().any(|i| i == identifier)
+        {
+            return;
+        }
+
+        self.identifiers.push(identifier.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identifiers(source: &str) -> Vec<String> {
+        let template = tera::Template::new("test", None, source).expect("valid template");
+
+        find_identifiers(&template.ast)
+    }
+
+    #[test]
+    fn identifiers_in_order() {
+        assert_eq!(
+            identifiers("{{ b }} {% if a and b %}{{ c | default(value=d) }}{% endif %}"),
+            ["b", "a", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn roots_of_identifiers() {
+        assert_eq!(
+            identifiers("{{ user.name }} {{ items[0] }} {{ user.email }}"),
+            ["user", "items"]
+        );
+    }
+
+    #[test]
+    fn loop_variables_are_scoped() {
+        assert_eq!(
+            identifiers(
+                "{% for key, item in items %}{{ key }}{{ item.name }}{{ loop.index }}\
+                 {% else %}{{ empty }}{% endfor %}{{ item }}"
+            ),
+            ["items", "empty", "item"]
+        );
+    }
+
+    #[test]
+    fn macro_arguments_are_scoped() {
+        assert_eq!(
+            identifiers(
+                "{% macro greet(name, greeting=\"Hi\") %}\
+                 {{ greeting }} {{ name }} {{ suffix }}{% endmacro %}{{ name }}"
+            ),
+            ["suffix", "name"]
+        );
+    }
+
+    #[test]
+    fn set_variables_are_scoped() {
+        assert_eq!(identifiers("{% set a = b %}{{ a }}"), ["b"]);
+        assert_eq!(
+            identifiers("{% for i in items %}{% set a = i %}{% endfor %}{{ a }}"),
+            ["items", "a"]
+        );
+        assert_eq!(
+            identifiers("{% for i in items %}{% set_global a = i %}{% endfor %}{{ a }}"),
+            ["items"]
+        );
+    }
+
+    #[test]
+    fn defined_tests_need_no_value() {
+        assert_eq!(
+            identifiers("{% if a is defined and b is not undefined %}{% endif %}"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            identifiers("{% if a is starting_with(b) %}{% endif %}"),
+            ["a", "b"]
+        );
+    }
+}
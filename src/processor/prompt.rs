@@ -1,62 +1,320 @@
-use anyhow::Result;
+use anyhow::{Error, Result};
+use regex::Regex;
+use tera::Value;
 
-use crate::template::{Template, config::Var};
+use crate::template::{
+    Template,
+    config::{ValueType, Var, VarKind, VarOption, number_value, parse_bool},
+};
 
-pub(super) fn prompt<'a>(template: &'a Template<'a>, identifier: &str) -> Result<String> {
-    let config = template.get_config()?;
-    let var = config.get_var(identifier)?;
-    let value = match var {
-        Var::Text {
-            identifier,
-            message,
+pub(super) fn prompt<'a>(template: &'a Template<'a>, var: Var) -> Result<Value> {
+    let text_rules = TextRules::try_from_var(&var)?;
+    let Var {
+        identifier,
+        message,
+        help_message,
+        description,
+        kind,
+        ..
+    } = var;
+    let identifier = identifier.as_str();
+
+    if let Some(description) = description {
+        cliclack::log::remark(description.trim_end())?;
+    }
+
+    // Inputs have no room for hints, so show the help message below the
+    // message instead.
+    let input_message = match (&message, &help_message) {
+        (Some(message), Some(help_message)) => {
+            Some(format!("{message}\n{}", console::style(help_message).dim()))
+        }
+        _ => message.clone(),
+    };
+    let input_message = input_message.as_deref();
+    let message = message.as_deref();
+    let value = match kind {
+        VarKind::Text {
             placeholder,
             initial_value,
             default,
             ..
         } => prompt_text(
             template,
-            identifier.as_ref(),
-            message.as_deref(),
+            identifier,
+            input_message,
             placeholder.as_deref(),
-            initial_value.as_deref(),
-            default.as_deref(),
-        )?,
-        Var::Select {
-            identifier: _,
+            initial_value.as_deref().or(default.as_deref()),
+            text_rules,
+        )?
+        .into(),
+        VarKind::Select { options, default } => prompt_select(
             message,
+            &options,
+            help_message.as_deref(),
+            default.as_deref(),
+        )?
+        .into(),
+        VarKind::Multiselect {
             options,
-            help_message,
-        } => prompt_select(message.as_deref(), &options, help_message.as_deref())?,
+            min,
+            max,
+            default,
+        } => prompt_multiselect(
+            message,
+            &options,
+            help_message.as_deref(),
+            (min, max),
+            default,
+        )?
+        .into(),
+        VarKind::Number {
+            placeholder,
+            default,
+            integer,
+            min,
+            max,
+            step,
+        } => prompt_number(
+            template,
+            identifier,
+            input_message,
+            placeholder.as_deref(),
+            default,
+            &NumberRules {
+                integer,
+                min,
+                max,
+                step,
+            },
+        )?,
+        VarKind::Confirm { default } => prompt_confirm(message, default)?.into(),
+        VarKind::Password { allow_empty } => {
+            prompt_password(template, identifier, input_message, allow_empty)?.into()
+        }
+        VarKind::Computed { .. } => {
+            return Err(Error::msg(format!(
+                "Computed variable {identifier:?} can't be prompted for"
+            )));
+        }
     };
 
     Ok(value)
 }
 
+/// Validate a value that was provided up front instead of prompted for.
+pub(super) fn answer<'a>(template: &'a Template<'a>, var: Var, value: &Value) -> Result<Value> {
+    let config = template.get_config()?;
+    let text_rules = TextRules::try_from_var(&var)?;
+    let identifier = var.identifier.as_str();
+    let string_value = match value {
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    };
+    let result = match var.kind {
+        VarKind::Text { .. } => match text_rules.check(&string_value) {
+            Ok(()) => template
+                .get_plugins()?
+                .validate(identifier, &string_value)?
+                .map(|()| string_value.into()),
+            Err(message) => Err(message),
+        },
+        VarKind::Select { options, .. } => {
+            if options.iter().any(|option| option.value == string_value) {
+                Ok(string_value.into())
+            } else {
+                Err(format!("Expected one of {}", option_values(&options)))
+            }
+        }
+        VarKind::Multiselect {
+            options, min, max, ..
+        } => {
+            let selected = parse_selection(value);
+
+            match selected
+                .iter()
+                .find(|value| !options.iter().any(|option| option.value == **value))
+            {
+                Some(value) => Err(format!(
+                    "Expected {value:?} to be one of {}",
+                    option_values(&options)
+                )),
+                None => check_selection(&selected, (min, max)).map(|()| selected.into()),
+            }
+        }
+        VarKind::Number {
+            integer,
+            min,
+            max,
+            step,
+            ..
+        } => {
+            let rules = NumberRules {
+                integer,
+                min,
+                max,
+                step,
+            };
+
+            match rules.parse(&string_value) {
+                Ok(number) => template
+                    .get_plugins()?
+                    .validate(identifier, &string_value)?
+                    .map(|()| number),
+                Err(message) => Err(message),
+            }
+        }
+        VarKind::Password { allow_empty } => {
+            if !allow_empty && string_value.is_empty() {
+                Err("Expected a value".to_string())
+            } else {
+                template
+                    .get_plugins()?
+                    .validate(identifier, &string_value)?
+                    .map(|()| string_value.into())
+            }
+        }
+        VarKind::Confirm { .. } => match value {
+            Value::Bool(value) => Ok((*value).into()),
+            _ => parse_bool(&string_value)
+                .map(Value::from)
+                .ok_or_else(|| "Expected true or false".to_string()),
+        },
+        VarKind::Computed { .. } => Err("Computed variables can't be provided".to_string()),
+    };
+
+    result.map_err(|message| {
+        if config.is_secret(identifier) {
+            Error::msg(format!("Invalid value for '{identifier}': {message}"))
+        } else {
+            Error::msg(format!(
+                "Invalid value {value} for '{identifier}': {message}"
+            ))
+        }
+    })
+}
+
+/// Constraints on the values for a text variable.
+#[derive(Default)]
+struct TextRules {
+    /// The pattern as it's declared along with the expression matching it
+    /// against the whole value.
+    pattern: Option<(String, Regex)>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    required: bool,
+    error: Option<String>,
+    /// The type the value needs to be convertible to.
+    value_type: Option<ValueType>,
+}
+
+impl TextRules {
+    /// Get the rules for a text variable, which are empty for other kinds of
+    /// variables.
+    fn try_from_var(var: &Var) -> Result<Self> {
+        let VarKind::Text {
+            pattern,
+            min_length,
+            max_length,
+            required,
+            error,
+            ..
+        } = &var.kind
+        else {
+            return Ok(Self::default());
+        };
+        let pattern = pattern
+            .as_ref()
+            .map(|pattern| {
+                Regex::new(&format!("^(?:{pattern})$"))
+                    .map(|regex| (pattern.clone(), regex))
+                    .map_err(|e| {
+                        Error::msg(format!("Pattern for {:?} is invalid: {e}", var.identifier))
+                    })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            pattern,
+            min_length: *min_length,
+            max_length: *max_length,
+            required: *required,
+            error: error.clone(),
+            value_type: var.value_type,
+        })
+    }
+
+    fn check(&self, value: &str) -> Result<(), String> {
+        self.check_rules(value)
+            .map_err(|message| self.error.clone().unwrap_or(message))?;
+
+        if let Some(value_type) = self.value_type {
+            value_type.convert(&value.into())?;
+        }
+
+        Ok(())
+    }
+
+    fn check_rules(&self, value: &str) -> Result<(), String> {
+        if value.is_empty() {
+            return if self.required {
+                Err("Expected a value".into())
+            } else {
+                Ok(())
+            };
+        }
+
+        let length = value.chars().count();
+
+        if let Some(min_length) = self.min_length.filter(|min_length| length < *min_length) {
+            return Err(format!("Expected at least {min_length} characters"));
+        }
+
+        if let Some(max_length) = self.max_length.filter(|max_length| length > *max_length) {
+            return Err(format!("Expected at most {max_length} characters"));
+        }
+
+        if let Some((pattern, _)) = self
+            .pattern
+            .as_ref()
+            .filter(|(_, regex)| !regex.is_match(value))
+        {
+            return Err(format!("Expected a value matching {pattern}"));
+        }
+
+        Ok(())
+    }
+}
+
 fn prompt_text(
     template: &Template,
     identifier: &str,
     message: Option<&str>,
     placeholder: Option<&str>,
     initial_value: Option<&str>,
-    default: Option<&str>,
+    rules: TextRules,
 ) -> Result<String> {
     let plugins = template.get_plugins()?;
     let message = message.unwrap_or("");
-    let mut prompt = cliclack::input(message);
+    let mut prompt = cliclack::input(message).required(rules.required);
     if let Some(placeholder) = placeholder {
         prompt = prompt.placeholder(placeholder);
     }
-    if let Some(value) = initial_value.or(default) {
+    if let Some(value) = initial_value {
         prompt = prompt.default_input(value);
     }
     let validator = {
         let plugins = plugins.clone();
         let ident = identifier.to_string();
 
-        move |input: &String| match plugins.validate(&ident, input) {
-            Ok(Ok(())) => Ok(()),
-            Ok(Err(message)) => Err(message),
-            Err(e) => Err(e.to_string()),
+        move |input: &String| {
+            rules.check(input)?;
+
+            match plugins.validate(&ident, input) {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(message)) => Err(message),
+                Err(e) => Err(e.to_string()),
+            }
         }
     };
     let mut prompt = prompt.validate(validator);
@@ -67,20 +325,339 @@ fn prompt_text(
 
 fn prompt_select(
     message: Option<&str>,
-    options: &[String],
+    options: &[VarOption],
     help_message: Option<&str>,
+    default: Option<&str>,
 ) -> Result<String> {
     let message = message.unwrap_or("");
     let mut prompt = cliclack::select(message);
     for (i, option) in options.iter().enumerate() {
-        let hint = if i == 0 {
-            help_message.unwrap_or("")
-        } else {
-            ""
+        let hint = option_hint(i, option, help_message);
+        prompt = prompt.item(option.value.clone(), option.label(), hint);
+    }
+    if let Some(default) = default {
+        prompt = prompt.initial_value(default.to_string());
+    }
+    let value = prompt.interact()?;
+
+    Ok(value)
+}
+
+/// Get the hint for an option, showing the help message on the first option
+/// when it doesn't have a hint of its own.
+fn option_hint<'a>(i: usize, option: &'a VarOption, help_message: Option<&'a str>) -> &'a str {
+    option
+        .hint
+        .as_deref()
+        .or(help_message.filter(|_| i == 0))
+        .unwrap_or("")
+}
+
+fn option_values(options: &[VarOption]) -> String {
+    options
+        .iter()
+        .map(|option| option.value.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Constraints on the values for a number variable.
+#[derive(Clone, Copy)]
+struct NumberRules {
+    integer: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+    step: Option<f64>,
+}
+
+impl NumberRules {
+    fn parse(&self, value: &str) -> Result<Value, String> {
+        let value = value.trim();
+        let number = match value.parse::<f64>() {
+            Ok(number) if number.is_finite() => number,
+            _ => return Err("Expected a number".into()),
         };
-        prompt = prompt.item(option.clone(), option, hint);
+
+        if self.integer && number.fract() != 0.0 {
+            return Err("Expected a whole number".into());
+        }
+
+        if let Some(min) = self.min.filter(|min| number < *min) {
+            return Err(format!("Expected a number of at least {min}"));
+        }
+
+        if let Some(max) = self.max.filter(|max| number > *max) {
+            return Err(format!("Expected a number of at most {max}"));
+        }
+
+        if let Some(step) = self.step.filter(|step| *step > 0.0) {
+            let steps = (number - self.min.unwrap_or(0.0)) / step;
+
+            if (steps - steps.round()).abs() > 1e-9 {
+                return Err(format!("Expected a number in steps of {step}"));
+            }
+        }
+
+        Ok(number_value(number))
+    }
+}
+
+fn prompt_number(
+    template: &Template,
+    identifier: &str,
+    message: Option<&str>,
+    placeholder: Option<&str>,
+    default: Option<f64>,
+    rules: &NumberRules,
+) -> Result<Value> {
+    let plugins = template.get_plugins()?;
+    let message = message.unwrap_or("");
+    let mut prompt = cliclack::input(message);
+    if let Some(placeholder) = placeholder {
+        prompt = prompt.placeholder(placeholder);
+    }
+    if let Some(default) = default {
+        prompt = prompt.default_input(&default.to_string());
+    }
+    let validator = {
+        let plugins = plugins.clone();
+        let ident = identifier.to_string();
+        let rules = *rules;
+
+        move |input: &String| {
+            rules.parse(input)?;
+
+            match plugins.validate(&ident, input) {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(message)) => Err(message),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    };
+    let mut prompt = prompt.validate(validator);
+    let value: String = prompt.interact()?;
+    let value = rules.parse(&value).map_err(Error::msg)?;
+
+    Ok(value)
+}
+
+fn prompt_confirm(message: Option<&str>, default: Option<bool>) -> Result<bool> {
+    let message = message.unwrap_or("");
+    let mut prompt = cliclack::confirm(message);
+    if let Some(default) = default {
+        prompt = prompt.initial_value(default);
     }
     let value = prompt.interact()?;
 
     Ok(value)
 }
+
+fn prompt_multiselect(
+    message: Option<&str>,
+    options: &[VarOption],
+    help_message: Option<&str>,
+    bounds: (Option<usize>, Option<usize>),
+    default: Vec<String>,
+) -> Result<Vec<String>> {
+    let message = message.unwrap_or("");
+    let mut prompt = cliclack::multiselect(message);
+    for (i, option) in options.iter().enumerate() {
+        let hint = option_hint(i, option, help_message);
+        prompt = prompt.item(option.value.clone(), option.label(), hint);
+    }
+    let mut prompt = prompt
+        .initial_values(default)
+        .required(bounds.0.is_some_and(|min| min > 0));
+    let value = loop {
+        let value = prompt.interact()?;
+
+        match check_selection(&value, bounds) {
+            Ok(()) => break value,
+            Err(message) => cliclack::log::error(message)?,
+        }
+    };
+
+    Ok(value)
+}
+
+/// Get the selected options from a list or a comma-separated string.
+fn parse_selection(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(values) => values
+            .iter()
+            .map(|value| match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            })
+            .collect(),
+        Value::String(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from)
+            .collect(),
+        value => vec![value.to_string()],
+    }
+}
+
+fn check_selection(
+    selected: &[String],
+    (min, max): (Option<usize>, Option<usize>),
+) -> Result<(), String> {
+    if let Some(min) = min.filter(|min| selected.len() < *min) {
+        return Err(format!("Select at least {min} options"));
+    }
+
+    if let Some(max) = max.filter(|max| selected.len() > *max) {
+        return Err(format!("Select at most {max} options"));
+    }
+
+    Ok(())
+}
+
+fn prompt_password(
+    template: &Template,
+    identifier: &str,
+    message: Option<&str>,
+    allow_empty: bool,
+) -> Result<String> {
+    let plugins = template.get_plugins()?;
+    let message = message.unwrap_or("");
+    let mut prompt = cliclack::password(message);
+    if allow_empty {
+        prompt = prompt.allow_empty();
+    }
+    let validator = {
+        let plugins = plugins.clone();
+        let ident = identifier.to_string();
+
+        move |input: &String| match plugins.validate(&ident, input) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(message)) => Err(message),
+            Err(e) => Err(e.to_string()),
+        }
+    };
+    let mut prompt = prompt.validate(validator);
+    let value = prompt.interact()?;
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_rules(var: &str) -> TextRules {
+        let var: Var = toml::from_str(&format!("identifier = \"name\"\ntype = \"text\"\n{var}"))
+            .expect("valid var");
+
+        TextRules::try_from_var(&var).expect("valid rules")
+    }
+
+    #[test]
+    fn text_is_required_by_default() {
+        assert!(text_rules("").check("").is_err());
+        assert!(text_rules("").check("x").is_ok());
+        assert!(text_rules("required = false").check("").is_ok());
+    }
+
+    #[test]
+    fn text_rules_only_apply_to_values() {
+        let rules = text_rules("required = false\nmin_length = 3\npattern = \"[a-z]+\"");
+
+        assert!(rules.check("").is_ok());
+        assert!(rules.check("ab").is_err());
+        assert!(rules.check("abc").is_ok());
+    }
+
+    #[test]
+    fn text_pattern_matches_whole_value() {
+        let rules = text_rules("pattern = \"[a-z]+|[0-9]+\"");
+
+        assert!(rules.check("abc").is_ok());
+        assert!(rules.check("123").is_ok());
+        assert!(rules.check("abc123").is_err());
+    }
+
+    #[test]
+    fn text_lengths_count_characters() {
+        let rules = text_rules("min_length = 2\nmax_length = 3");
+
+        assert!(rules.check("é").is_err());
+        assert!(rules.check("éé").is_ok());
+        assert!(rules.check("éééé").is_err());
+    }
+
+    #[test]
+    fn text_error_replaces_message() {
+        let rules = text_rules("pattern = \"[a-z]+\"\nerror = \"Use lowercase letters\"");
+
+        assert_eq!(rules.check("ABC"), Err("Use lowercase letters".into()));
+    }
+
+    #[test]
+    fn text_value_type_is_checked() {
+        let rules = text_rules("value_type = \"int\"");
+
+        assert!(rules.check("42").is_ok());
+        assert!(rules.check("4.2").is_err());
+    }
+
+    fn number_rules(integer: bool, min: Option<f64>, step: Option<f64>) -> NumberRules {
+        NumberRules {
+            integer,
+            min,
+            max: Some(10.0),
+            step,
+        }
+    }
+
+    #[test]
+    fn number_is_parsed() {
+        let rules = number_rules(false, None, None);
+
+        assert_eq!(rules.parse(" 5 "), Ok(Value::from(5)));
+        assert_eq!(rules.parse("2.5"), Ok(Value::from(2.5)));
+        assert_eq!(rules.parse("1e1"), Ok(Value::from(10)));
+        assert!(rules.parse("").is_err());
+        assert!(rules.parse("NaN").is_err());
+        assert!(rules.parse("-inf").is_err());
+    }
+
+    #[test]
+    fn number_is_whole() {
+        let rules = number_rules(true, None, None);
+
+        assert_eq!(rules.parse("3.0"), Ok(Value::from(3)));
+        assert!(rules.parse("3.5").is_err());
+    }
+
+    #[test]
+    fn number_is_within_bounds() {
+        let rules = number_rules(false, Some(-1.5), None);
+
+        assert!(rules.parse("-1.5").is_ok());
+        assert!(rules.parse("-1.6").is_err());
+        assert!(rules.parse("10").is_ok());
+        assert!(rules.parse("10.01").is_err());
+    }
+
+    #[test]
+    fn number_steps_start_at_min() {
+        let rules = number_rules(false, Some(1.0), Some(2.0));
+
+        assert!(rules.parse("1").is_ok());
+        assert!(rules.parse("5").is_ok());
+        assert!(rules.parse("4").is_err());
+    }
+
+    #[test]
+    fn number_steps_allow_float_errors() {
+        let rules = number_rules(false, None, Some(0.1));
+
+        assert!(rules.parse("0.3").is_ok());
+        assert!(rules.parse("0.7").is_ok());
+        assert!(rules.parse("0.35").is_err());
+        assert!(number_rules(false, None, Some(0.0)).parse("0.35").is_ok());
+    }
+}
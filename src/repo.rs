@@ -1,8 +1,17 @@
-use anyhow::Result;
+use anyhow::{Error, Result};
 use log::info;
-use std::path::Path;
+use std::{
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-pub(crate) fn clone(repo_url: &str, dst: &Path) -> Result<()> {
+/// The file in the git directory of a clone that holds when its remote was
+/// last fetched.
+const FETCHED_FILENAME: &str = "spwn-fetched";
+
+/// Clone a repository, checking out `reference` instead of the remote `HEAD`
+/// when provided. The reference can be a branch, a tag or a commit.
+pub(crate) fn clone(repo_url: &str, dst: &Path, reference: Option<&str>) -> Result<()> {
     // SAFETY: The closure doesn't use mutexes or memory allocation, so it should be safe to call from a signal handler.
     unsafe {
         gix::interrupt::init_handler(1, || {})?;
@@ -11,13 +20,25 @@ pub(crate) fn clone(repo_url: &str, dst: &Path) -> Result<()> {
     let url = gix::url::parse(repo_url.into())?;
 
     info!("Url: {:?}", url.to_bstring());
+    info!("Cloning {repo_url:?} into {dst:?}...");
 
-    let mut prepare_clone = gix::prepare_clone(url, dst)?;
+    // A reference is tried as a branch or tag first, as a name made of hex
+    // digits can be either, and only then resolved as a commit. A full commit
+    // id can't be fetched by name, so it's resolved right away.
+    let mut prepare_checkout = match reference {
+        None => fetch(url, dst, None)?,
+        Some(reference) if is_object_id(reference) => fetch_commit(url, dst, reference)?,
+        Some(reference) => match fetch(url.clone(), dst, Some(reference)) {
+            Ok(prepare_checkout) => prepare_checkout,
+            Err(e) => {
+                info!("Reference {reference:?} isn't a branch or tag: {e}");
 
-    info!("Cloning {repo_url:?} into {dst:?}...");
+                std::fs::create_dir_all(dst)?;
 
-    let (mut prepare_checkout, _) = prepare_clone
-        .fetch_then_checkout(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
+                fetch_commit(url, dst, reference)?
+            }
+        },
+    };
 
     if let Some(work_dir) = prepare_checkout.repo().workdir() {
         info!("Checking out into {work_dir:?} ...");
@@ -30,6 +51,8 @@ pub(crate) fn clone(repo_url: &str, dst: &Path) -> Result<()> {
         info!("Repo cloned into {work_dir:?}");
     }
 
+    mark_fetched(&repo)?;
+
     if let Some(remote) = repo.find_default_remote(gix::remote::Direction::Fetch) {
         let remote = remote?;
 
@@ -41,3 +64,207 @@ pub(crate) fn clone(repo_url: &str, dst: &Path) -> Result<()> {
 
     Ok(())
 }
+
+/// Fetch a repository into `dst`, checking out `ref_name` when provided.
+fn fetch(url: gix::Url, dst: &Path, ref_name: Option<&str>) -> Result<gix::clone::PrepareCheckout> {
+    let mut prepare_clone = gix::prepare_clone(url, dst)?;
+
+    if let Some(ref_name) = ref_name {
+        info!("Using reference {ref_name:?}");
+
+        prepare_clone = prepare_clone.with_ref_name(Some(ref_name))?;
+    }
+
+    let (prepare_checkout, _) = prepare_clone
+        .fetch_then_checkout(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
+
+    Ok(prepare_checkout)
+}
+
+/// Fetch a repository into `dst`, checking out `commit`.
+fn fetch_commit(url: gix::Url, dst: &Path, commit: &str) -> Result<gix::clone::PrepareCheckout> {
+    let prepare_checkout = fetch(url, dst, None)?;
+
+    detach_head(prepare_checkout.repo(), commit).map_err(|_| {
+        Error::msg(format!(
+            "Reference {commit:?} isn't a branch, tag or commit"
+        ))
+    })?;
+
+    Ok(prepare_checkout)
+}
+
+/// Fetch the remote of an existing clone and fast-forward its worktree to the
+/// fetched branch or tag. Clones of a commit are left as they are.
+pub(crate) fn update(dst: &Path) -> Result<()> {
+    use gix::remote::Direction;
+
+    let mut repo = gix::open(dst)?;
+
+    // Updating references is logged, which requires a committer.
+    repo.committer_or_set_generic_fallback()?;
+
+    let Some(head_name) = repo.head_name()? else {
+        info!("Not updating {dst:?} as it's checked out at a commit");
+
+        return mark_fetched(&repo);
+    };
+    let remote = repo
+        .find_default_remote(Direction::Fetch)
+        .ok_or_else(|| Error::msg(format!("Clone in {dst:?} doesn't have a remote")))??;
+    let remote_name = remote
+        .name()
+        .map_or_else(|| "origin".to_string(), |name| name.as_bstr().to_string());
+    let head_name = head_name.as_bstr().to_string();
+    // Fetch into a reference that isn't checked out, as those can't be
+    // updated by fetching.
+    let tracking_name = match head_name.strip_prefix("refs/heads/") {
+        Some(branch) => format!("refs/remotes/{remote_name}/{branch}"),
+        None => format!("refs/spwn/{}", head_name.trim_start_matches("refs/")),
+    };
+    let refspec = format!("+{head_name}:{tracking_name}");
+    let current = repo.head_id()?.detach();
+
+    info!("Fetching {refspec:?} into {dst:?}...");
+
+    remote
+        .with_refspecs([refspec.as_str()], Direction::Fetch)?
+        .connect(Direction::Fetch)?
+        .prepare_fetch(
+            gix::progress::Discard,
+            gix::remote::ref_map::Options::default(),
+        )?
+        .receive(gix::progress::Discard, &gix::interrupt::IS_INTERRUPTED)?;
+
+    let target = repo
+        .find_reference(tracking_name.as_str())?
+        .peel_to_id()?
+        .detach();
+
+    if current == target {
+        info!("Clone in {dst:?} is up to date at {current}");
+    } else {
+        info!("Updating clone in {dst:?} from {current} to {target}");
+
+        checkout(&repo, target)?;
+        set_reference(&repo, &head_name, target)?;
+    }
+
+    mark_fetched(&repo)
+}
+
+/// Replace the worktree and index of a clone with the tree of a commit,
+/// removing the files that are no longer part of it.
+fn checkout(repo: &gix::Repository, commit: gix::ObjectId) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| Error::msg("Clone doesn't have a worktree"))?;
+    let tree = repo.find_commit(commit)?.tree_id()?.detach();
+    let previous = repo.open_index()?;
+    let mut index = repo.index_from_tree(&tree)?;
+
+    for entry in previous.entries() {
+        let path = entry.path(&previous);
+
+        if index.entry_by_path(path).is_none() {
+            let path = workdir.join(gix::path::from_bstr(path));
+
+            info!("Removing {path:?}");
+
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    let mut options =
+        repo.checkout_options(gix::worktree::stack::state::attributes::Source::IdMapping)?;
+
+    options.overwrite_existing = true;
+
+    gix::worktree::state::checkout(
+        &mut index,
+        workdir,
+        repo.objects.clone().into_arc()?,
+        &gix::progress::Discard,
+        &gix::progress::Discard,
+        &gix::interrupt::IS_INTERRUPTED,
+        options,
+    )?;
+    index.write(gix::index::write::Options::default())?;
+
+    Ok(())
+}
+
+/// Get when the remote of a clone was last fetched.
+pub(crate) fn fetched_at(dir: &Path) -> Option<SystemTime> {
+    let repo = gix::open(dir).ok()?;
+    let seconds = std::fs::read_to_string(repo.git_dir().join(FETCHED_FILENAME)).ok()?;
+
+    Some(UNIX_EPOCH + Duration::from_secs(seconds.trim().parse().ok()?))
+}
+
+fn mark_fetched(repo: &gix::Repository) -> Result<()> {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    std::fs::write(repo.git_dir().join(FETCHED_FILENAME), seconds.to_string())?;
+
+    Ok(())
+}
+
+pub(crate) fn head_commit(dir: &Path) -> Result<String> {
+    let repo = gix::open(dir)?;
+    let id = repo.head_id()?;
+
+    Ok(id.to_string())
+}
+
+/// Point `HEAD` at a commit so it is used when checking out the worktree.
+fn detach_head(repo: &gix::Repository, commit: &str) -> Result<()> {
+    let id = repo.rev_parse_single(commit)?.detach();
+
+    info!("Using commit {id}");
+
+    set_reference(repo, "HEAD", id)
+}
+
+fn set_reference(repo: &gix::Repository, name: &str, id: gix::ObjectId) -> Result<()> {
+    use gix::refs::{
+        Target,
+        transaction::{Change, LogChange, PreviousValue, RefEdit},
+    };
+
+    repo.edit_reference(RefEdit {
+        change: Change::Update {
+            log: LogChange::default(),
+            expected: PreviousValue::Any,
+            new: Target::Object(id),
+        },
+        name: name.try_into()?,
+        deref: false,
+    })?;
+
+    Ok(())
+}
+
+/// Whether a reference is a full object id rather than a name.
+fn is_object_id(reference: &str) -> bool {
+    gix::ObjectId::from_hex(reference.as_bytes()).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_commit_ids_are_object_ids() {
+        assert!(is_object_id("0123456789abcdef0123456789abcdef01234567"));
+        assert!(is_object_id("0123456789ABCDEF0123456789ABCDEF01234567"));
+    }
+
+    #[test]
+    fn names_are_not_object_ids() {
+        assert!(!is_object_id("v1"));
+        assert!(!is_object_id("cafe1234"));
+        assert!(!is_object_id("0123456789abcdef0123456789abcdef0123456"));
+        assert!(!is_object_id("0123456789abcdef0123456789abcdef0123456g"));
+    }
+}
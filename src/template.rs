@@ -2,9 +2,13 @@ pub(crate) mod config;
 pub(crate) mod plugins;
 
 use anyhow::{Error, Result};
-use log::info;
+use log::{info, warn};
 use plugins::Plugins;
-use std::{path::PathBuf, sync::OnceLock};
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
 
 use crate::config::cache_dir;
 use crate::template::config::Config;
@@ -15,39 +19,199 @@ const CONFIG_FILENAME: &str = "config.toml";
 const PLUGINS_FILENAME: &str = "plugins.scm";
 const INFO_FILENAME: &str = "info.txt";
 
+/// How to treat a clone of the template that is already in the cache.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum CachePolicy {
+    /// Use the cached clone, refreshing it when it's older than the max age.
+    MaxAge(Option<Duration>),
+    /// Always refresh the cached clone.
+    Refresh,
+    /// Only use the cached clone and never access the network.
+    Offline,
+}
+
 pub(crate) struct Template<'a> {
     pub uri: String,
+    /// The branch, tag or commit to check out.
+    pub reference: Option<String>,
     pub hash: String,
+    /// The directory within the repository containing the template.
+    pub subdir: Option<String>,
+    /// The directory of a template that is used in place instead of cloned.
+    local_dir: Option<PathBuf>,
     config: OnceLock<Config<'a>>,
     plugins: OnceLock<Plugins>,
     info: OnceLock<Option<String>>,
 }
 
 impl<'a> Template<'a> {
+    /// Create a template from a URI, which can end with `@<ref>` to select a
+    /// branch, tag or commit and `#<path>` to select a directory within the
+    /// repository.
+    ///
+    /// A URI pointing to an existing local directory is used in place, so
+    /// changes to it are picked up on every run.
     pub fn new(uri: String) -> Self {
-        let hash = create_hash(&uri);
+        let (uri, subdir) = split_subdir(uri);
+        let local_dir = Path::new(&uri)
+            .is_dir()
+            .then(|| std::fs::canonicalize(&uri).ok())
+            .flatten();
+        let (uri, reference) = if local_dir.is_some() {
+            (uri, None)
+        } else {
+            split_reference(uri)
+        };
+        let hash = create_hash(&uri, reference.as_deref());
 
         Template {
             uri,
+            reference,
             hash,
+            subdir,
+            local_dir,
             config: OnceLock::new(),
             plugins: OnceLock::new(),
             info: OnceLock::new(),
         }
     }
 
-    pub fn init(self) -> Result<Self> {
+    /// Use a reference instead of the one provided with the URI.
+    pub fn with_reference(mut self, reference: Option<String>) -> Self {
+        if reference.is_some() {
+            self.hash = create_hash(&self.uri, reference.as_deref());
+            self.reference = reference;
+        }
+
+        self
+    }
+
+    /// Use a directory within the repository instead of the one provided with
+    /// the URI.
+    pub fn with_subdir(mut self, subdir: Option<String>) -> Self {
+        if subdir.is_some() {
+            self.subdir = subdir;
+        }
+
+        self
+    }
+
+    pub fn init(self, cache_policy: CachePolicy) -> Result<Self> {
+        if let Some(local_dir) = &self.local_dir {
+            if self.reference.is_some() {
+                warn!("Ignoring reference for local template");
+            }
+
+            info!("Using local template {local_dir:?}");
+
+            return self.check_subdir();
+        }
+
         let cache_dir = self.cache_dir()?;
 
-        if cache_dir.is_dir() {
+        if !cache_dir.is_dir() {
+            if let CachePolicy::Offline = cache_policy {
+                return Err(Error::msg(format!(
+                    "Template {:?} isn't cached and can't be cloned while offline",
+                    self.uri
+                )));
+            }
+
+            crate::repo::clone(&self.uri, &cache_dir, self.reference.as_deref())?;
+
+            return self.check_subdir();
+        }
+
+        match cache_policy {
+            CachePolicy::Refresh => crate::repo::update(&cache_dir)?,
+            CachePolicy::Offline | CachePolicy::MaxAge(None) => {}
+            CachePolicy::MaxAge(Some(max_age)) => {
+                let age = crate::repo::fetched_at(&cache_dir)
+                    .and_then(|fetched_at| fetched_at.elapsed().ok());
+
+                info!("Cached clone was fetched {age:?} ago");
+
+                // Fall back to the cached clone when it can't be updated, as
+                // it was still usable before it expired.
+                if age.is_none_or(|age| age > max_age)
+                    && let Err(e) = crate::repo::update(&cache_dir)
+                {
+                    cliclack::log::warning(format!(
+                        "Using the cached clone as it couldn't be updated: {e}"
+                    ))?;
+                }
+            }
+        }
+
+        self.check_subdir()
+    }
+
+    fn check_subdir(self) -> Result<Self> {
+        let Some(subdir) = &self.subdir else {
             return Ok(self);
+        };
+
+        if !self.root_dir()?.is_dir() {
+            return Err(Error::msg(format!(
+                "Template {:?} doesn't contain a directory {subdir:?}",
+                self.uri
+            )));
         }
 
-        crate::repo::clone(&self.uri, &cache_dir)?;
+        info!("Using template in {subdir:?}");
 
         Ok(self)
     }
 
+    /// Get the commit of the template that is used, which is unknown for
+    /// local templates.
+    pub fn get_commit(&self) -> Result<Option<String>> {
+        if self.local_dir.is_some() {
+            return Ok(None);
+        }
+
+        let commit = crate::repo::head_commit(&self.cache_dir()?)?;
+
+        Ok(Some(commit))
+    }
+
+    /// Get the directory containing the files of the template.
+    pub fn root_dir(&self) -> Result<PathBuf> {
+        let mut root_dir = match &self.local_dir {
+            Some(local_dir) => local_dir.clone(),
+            None => self.cache_dir()?,
+        };
+
+        if let Some(subdir) = &self.subdir {
+            root_dir.push(subdir);
+        }
+
+        Ok(root_dir)
+    }
+
+    /// Find the directories in the template containing a config file, which
+    /// can be selected as templates of their own.
+    pub fn find_templates(&self) -> Result<Vec<PathBuf>> {
+        let root_dir = self.root_dir()?;
+        let templates = walkdir::WalkDir::new(&root_dir)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry.file_type().is_dir()
+                    && entry
+                        .path()
+                        .join(CONFIG_DIR)
+                        .join(CONFIG_FILENAME)
+                        .is_file()
+            })
+            .filter_map(|entry| pathdiff::diff_paths(entry.path(), &root_dir))
+            .collect();
+
+        Ok(templates)
+    }
+
     pub fn cache_dir(&self) -> Result<PathBuf> {
         let Some(mut cache_dir) = cache_dir() else {
             return Err(Error::msg("No cache directory"));
@@ -59,13 +223,13 @@ impl<'a> Template<'a> {
     }
 
     pub fn config_dir(&self) -> Result<PathBuf> {
-        let config_dir = self.cache_dir()?.as_path().join(CONFIG_DIR);
+        let config_dir = self.root_dir()?.as_path().join(CONFIG_DIR);
 
         Ok(config_dir)
     }
 
     pub fn get_ignore(&self) -> Result<Vec<String>> {
-        let template_ignore_file = self.cache_dir()?.as_path().join(IGNORE_FILENAME);
+        let template_ignore_file = self.root_dir()?.as_path().join(IGNORE_FILENAME);
 
         if !template_ignore_file.is_file() {
             return Err(Error::msg("No template ignore file"));
@@ -125,12 +289,158 @@ impl<'a> Template<'a> {
     }
 }
 
-fn create_hash(path: &str) -> String {
+/// Split a directory like `#templates/rust` from the end of a URI.
+fn split_subdir(uri: String) -> (String, Option<String>) {
+    if Path::new(&uri).is_dir() {
+        return (uri, None);
+    }
+
+    let Some((base, subdir)) = uri.rsplit_once('#') else {
+        return (uri, None);
+    };
+    let subdir = subdir.trim_matches('/');
+
+    if subdir.is_empty() {
+        return (base.to_string(), None);
+    }
+
+    let subdir = Some(subdir.to_string());
+    let uri = base.to_string();
+
+    (uri, subdir)
+}
+
+/// Split a reference like `@v2.1` from the end of a URI.
+///
+/// An `@` is only used as a separator when what follows can't be part of the
+/// URI itself, so user info like in `git@github.com:user/repo.git` or
+/// `https://user@host/repo.git` is left alone.
+fn split_reference(uri: String) -> (String, Option<String>) {
+    let Some((base, reference)) = uri.rsplit_once('@') else {
+        return (uri, None);
+    };
+    let path = base.split_once("://").map_or(base, |(_, path)| path);
+
+    if reference.is_empty() || reference.contains(':') || !path.contains(['/', ':']) {
+        return (uri, None);
+    }
+
+    let reference = Some(reference.to_string());
+    let uri = base.to_string();
+
+    (uri, reference)
+}
+
+fn create_hash(path: &str, reference: Option<&str>) -> String {
     use sha2::{Digest, Sha256};
 
     let mut hasher = Sha256::new();
 
     hasher.update(path);
 
+    if let Some(reference) = reference {
+        hasher.update("@");
+        hasher.update(reference);
+    }
+
     format!("{:x}", hasher.finalize())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference(uri: &str) -> (String, Option<String>) {
+        split_reference(uri.to_string())
+    }
+
+    #[test]
+    fn split_reference_from_uri() {
+        assert_eq!(
+            reference("https://host/repo.git@v1"),
+            ("https://host/repo.git".into(), Some("v1".into()))
+        );
+        assert_eq!(
+            reference("git@host:repo.git@v1"),
+            ("git@host:repo.git".into(), Some("v1".into()))
+        );
+        assert_eq!(
+            reference("user/repo@feature/x"),
+            ("user/repo".into(), Some("feature/x".into()))
+        );
+    }
+
+    #[test]
+    fn split_reference_keeps_user_info() {
+        assert_eq!(
+            reference("git@host:repo.git"),
+            ("git@host:repo.git".into(), None)
+        );
+        assert_eq!(
+            reference("https://user@host/repo.git"),
+            ("https://user@host/repo.git".into(), None)
+        );
+        assert_eq!(
+            reference("ssh://git@host:22/repo.git"),
+            ("ssh://git@host:22/repo.git".into(), None)
+        );
+    }
+
+    #[test]
+    fn split_reference_without_reference() {
+        assert_eq!(
+            reference("https://host/repo.git"),
+            ("https://host/repo.git".into(), None)
+        );
+        assert_eq!(
+            reference("https://host/repo.git@"),
+            ("https://host/repo.git@".into(), None)
+        );
+    }
+
+    fn subdir(uri: &str) -> (String, Option<String>) {
+        split_subdir(uri.to_string())
+    }
+
+    #[test]
+    fn split_subdir_from_uri() {
+        assert_eq!(
+            subdir("https://host/repo.git#templates/rust"),
+            (
+                "https://host/repo.git".into(),
+                Some("templates/rust".into())
+            )
+        );
+        assert_eq!(
+            subdir("git@host:repo.git@v1#/rust/"),
+            ("git@host:repo.git@v1".into(), Some("rust".into()))
+        );
+    }
+
+    #[test]
+    fn split_subdir_without_subdir() {
+        assert_eq!(
+            subdir("https://host/repo.git"),
+            ("https://host/repo.git".into(), None)
+        );
+        assert_eq!(
+            subdir("https://host/repo.git#"),
+            ("https://host/repo.git".into(), None)
+        );
+        assert_eq!(
+            subdir("https://host/repo.git#/"),
+            ("https://host/repo.git".into(), None)
+        );
+    }
+
+    #[test]
+    fn split_subdir_before_reference() {
+        let (uri, subdir) = subdir("https://user@host/repo.git@v1#rust");
+
+        assert_eq!(subdir, Some("rust".into()));
+        assert_eq!(
+            split_reference(uri),
+            ("https://user@host/repo.git".into(), Some("v1".into()))
+        );
+    }
+}
@@ -1,7 +1,8 @@
-use anyhow::Result;
+use anyhow::{Error, Result};
 use log::info;
 use serde::Deserialize;
 use std::path::Path;
+use tera::Value;
 
 use super::{CONFIG_FILENAME, Template};
 
@@ -31,70 +32,128 @@ impl<'a> Config<'a> {
         })
     }
 
-    pub(crate) fn get_var(&self, identifier: &str) -> Result<Var> {
-        let var = self.config_file.get_var(identifier);
+    /// Get a variable with plugins applied, rendering the templates in its
+    /// text defaults before passing them to the plugins.
+    pub(crate) fn get_var(
+        &self,
+        identifier: &str,
+        mut render: impl FnMut(&str) -> Result<String>,
+    ) -> Result<Var> {
+        let mut var = self.config_file.get_var(identifier);
         let plugins = self.template.get_plugins()?;
         let default_message = format!("Provide a value for '{identifier}':");
 
-        let var = match var {
-            Var::Text {
-                identifier: _,
-                message,
-                help_message,
+        match &mut var.kind {
+            VarKind::Text {
                 placeholder,
                 initial_value,
                 default,
+                ..
             } => {
-                let message = match message {
-                    Some(message) => message,
-                    None => default_message,
-                };
-                let message = plugins.message(identifier, &message)?;
-                let help_message = plugins.help_message(identifier, help_message.as_deref())?;
-                let placeholder = plugins.placeholder(identifier, placeholder.as_deref())?;
-                let initial_value = plugins.initial_value(identifier, initial_value.as_deref())?;
-                let default = plugins.default(identifier, default.as_deref())?;
-
-                Var::Text {
-                    identifier: identifier.to_string(),
-                    message: Some(message),
-                    help_message,
-                    placeholder,
-                    initial_value,
-                    default,
+                for value in [&mut *placeholder, &mut *initial_value, &mut *default]
+                    .into_iter()
+                    .flatten()
+                {
+                    *value = render(value)?;
                 }
+
+                *placeholder = plugins.placeholder(identifier, placeholder.as_deref())?;
+                *initial_value = plugins.initial_value(identifier, initial_value.as_deref())?;
+                *default = plugins.default(identifier, default.as_deref())?;
+            }
+            VarKind::Select { options, default } => {
+                *options = plugins.options(identifier, options)?;
+                *default = plugins.default(identifier, default.as_deref())?;
+            }
+            VarKind::Multiselect { options, .. } => {
+                *options = plugins.options(identifier, options)?;
             }
-            Var::Select {
-                identifier: _,
-                message,
-                options,
-                help_message,
+            VarKind::Number {
+                placeholder,
+                default,
+                ..
             } => {
-                let message = match message {
-                    Some(message) => message,
-                    None => default_message,
-                };
-                let message = plugins.message(identifier, &message)?;
-                let options = plugins.options(identifier, &options)?;
-                let help_message = plugins.help_message(identifier, help_message.as_deref())?;
-
-                Var::Select {
-                    identifier: identifier.to_string(),
-                    message: Some(message),
-                    options,
-                    help_message,
-                }
+                let value = default.map(|default| default.to_string());
+
+                *placeholder = plugins.placeholder(identifier, placeholder.as_deref())?;
+                *default = plugins
+                    .default(identifier, value.as_deref())?
+                    .map(|default| {
+                        default.trim().parse().map_err(|_| {
+                            Error::msg(format!("Default for {identifier:?} should be a number"))
+                        })
+                    })
+                    .transpose()?;
             }
-        };
+            VarKind::Confirm { default } => {
+                let value = default.map(|default| default.to_string());
+
+                *default = plugins
+                    .default(identifier, value.as_deref())?
+                    .map(|default| {
+                        parse_bool(&default).ok_or_else(|| {
+                            Error::msg(format!(
+                                "Default for {identifier:?} should be \"true\" or \"false\""
+                            ))
+                        })
+                    })
+                    .transpose()?;
+            }
+            VarKind::Password { .. } | VarKind::Computed { .. } => {}
+        }
+
+        let message = var.message.as_deref().unwrap_or(&default_message);
+
+        var.message = Some(plugins.message(identifier, message)?);
+        var.help_message = plugins.help_message(identifier, var.help_message.as_deref())?;
+        var.identifier = identifier.to_string();
 
         Ok(var)
     }
+
+    /// Get a variable as it's declared in the config file, without applying
+    /// any plugins.
+    pub(crate) fn get_declared_var(&self, identifier: &str) -> Option<&Var> {
+        self.config_file.find_var(identifier)
+    }
+
+    /// Get the identifiers of the declared variables in the order they should
+    /// be prompted for.
+    pub(crate) fn get_declared_identifiers(&self) -> Vec<&str> {
+        let mut vars = self.config_file.vars.iter().collect::<Vec<_>>();
+
+        vars.sort_by_key(|var| var.order.unwrap_or(0));
+
+        vars.iter().map(|var| var.identifier.as_str()).collect()
+    }
+
+    /// Get the rules for including files.
+    pub(crate) fn get_file_rules(&self) -> &[FileRule] {
+        &self.config_file.files
+    }
+
+    /// Whether the value of a variable is a secret that shouldn't be shown.
+    pub(crate) fn is_secret(&self, identifier: &str) -> bool {
+        self.get_declared_var(identifier)
+            .is_some_and(|var| matches!(var.kind, VarKind::Password { .. }))
+    }
 }
 
 #[derive(Default, Deserialize)]
 struct ConfigFile {
     #[serde(rename = "var", default = "Vec::new")]
     vars: Vec<Var>,
+    #[serde(rename = "file", default = "Vec::new")]
+    files: Vec<FileRule>,
+}
+
+/// A rule to only include the files and directories matching a glob when a
+/// condition is met.
+#[derive(Deserialize)]
+pub(crate) struct FileRule {
+    pub(crate) path: String,
+    /// A Tera expression or template like the `when` of a variable.
+    pub(crate) when: String,
 }
 
 impl ConfigFile {
@@ -105,51 +164,310 @@ impl ConfigFile {
         Ok(config)
     }
 
+    fn find_var(&self, identifier: &str) -> Option<&Var> {
+        self.vars.iter().find(|v| v.identifier == identifier)
+    }
+
     fn get_var(&self, identifier: &str) -> Var {
-        self.vars
-            .iter()
-            .find(|v| Var::has_identifier(v, identifier))
-            .cloned()
-            .unwrap_or_default()
+        self.find_var(identifier).cloned().unwrap_or_default()
     }
 }
 
+#[derive(Clone, Default, Deserialize)]
+pub(crate) struct Var {
+    pub(crate) identifier: String,
+    pub(crate) message: Option<String>,
+    pub(crate) help_message: Option<String>,
+    /// A longer explanation that is shown before prompting, which can span
+    /// multiple lines.
+    pub(crate) description: Option<String>,
+    /// A Tera expression that needs to be truthy for the variable to be
+    /// prompted for.
+    pub(crate) when: Option<String>,
+    /// Variables with a lower order are prompted for first, defaulting to zero
+    /// and falling back to the order in which they're declared.
+    pub(crate) order: Option<i64>,
+    /// The type to convert the value to before it's added to the context.
+    pub(crate) value_type: Option<ValueType>,
+    #[serde(flatten)]
+    pub(crate) kind: VarKind,
+}
+
 #[derive(Clone, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
-pub(crate) enum Var {
+pub(crate) enum VarKind {
+    /// Free text, where the placeholder, initial value and default can use
+    /// the values of other variables.
     Text {
-        identifier: String,
-        message: Option<String>,
-        help_message: Option<String>,
         placeholder: Option<String>,
         initial_value: Option<String>,
         default: Option<String>,
+        /// A regular expression the whole value needs to match.
+        pattern: Option<String>,
+        min_length: Option<usize>,
+        max_length: Option<usize>,
+        /// Whether an empty value is rejected, which is the default. The other
+        /// rules only apply to values that aren't empty.
+        #[serde(default = "default_required")]
+        required: bool,
+        /// The message to show instead of the default one when the value
+        /// doesn't follow the rules above.
+        error: Option<String>,
     },
     Select {
-        identifier: String,
-        message: Option<String>,
-        options: Vec<String>,
-        help_message: Option<String>,
+        options: Vec<VarOption>,
+        /// The value of the option that is selected initially.
+        default: Option<String>,
+    },
+    Multiselect {
+        options: Vec<VarOption>,
+        /// The minimum number of options to select.
+        min: Option<usize>,
+        /// The maximum number of options to select.
+        max: Option<usize>,
+        /// The options that are selected initially.
+        #[serde(default)]
+        default: Vec<String>,
+    },
+    Number {
+        placeholder: Option<String>,
+        default: Option<f64>,
+        /// Whether only whole numbers are allowed.
+        #[serde(default)]
+        integer: bool,
+        min: Option<f64>,
+        max: Option<f64>,
+        /// The increment between allowed values, starting at `min` or zero.
+        step: Option<f64>,
+    },
+    Confirm {
+        default: Option<bool>,
+    },
+    /// A secret that is entered masked and never logged.
+    Password {
+        /// Whether an empty value is allowed.
+        #[serde(default)]
+        allow_empty: bool,
+    },
+    /// A value that is never prompted for but rendered from the values of
+    /// other variables. Without a value it's provided by the `default` or
+    /// `finalize-context` plugin functions instead.
+    Computed {
+        value: Option<String>,
+    },
+}
+
+/// An option for a select or multiselect variable, declared as either just
+/// its value or a table with a label and hint to show instead.
+#[derive(Clone, Deserialize)]
+#[serde(from = "OptionEntry")]
+pub(crate) struct VarOption {
+    pub(crate) value: String,
+    pub(crate) label: Option<String>,
+    pub(crate) hint: Option<String>,
+}
+
+impl VarOption {
+    pub(crate) fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.value)
+    }
+}
+
+impl From<String> for VarOption {
+    fn from(value: String) -> Self {
+        Self {
+            value,
+            label: None,
+            hint: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OptionEntry {
+    Value(String),
+    Table {
+        value: String,
+        label: Option<String>,
+        hint: Option<String>,
     },
 }
 
+impl From<OptionEntry> for VarOption {
+    fn from(entry: OptionEntry) -> Self {
+        match entry {
+            OptionEntry::Value(value) => value.into(),
+            OptionEntry::Table { value, label, hint } => Self { value, label, hint },
+        }
+    }
+}
+
 impl Var {
-    fn has_identifier(&self, name: &str) -> bool {
-        match self {
-            Var::Text { identifier, .. } | Var::Select { identifier, .. } => identifier == name,
+    /// Get the value to use when the variable isn't prompted for.
+    pub(crate) fn default_value(&self) -> Option<Value> {
+        match &self.kind {
+            VarKind::Text { default, .. } | VarKind::Select { default, .. } => {
+                default.clone().map(Value::from)
+            }
+            VarKind::Multiselect { default, .. } => {
+                (!default.is_empty()).then(|| default.clone().into())
+            }
+            VarKind::Number { default, .. } => default.map(number_value),
+            VarKind::Confirm { default } => default.map(Value::from),
+            VarKind::Password { .. } | VarKind::Computed { .. } => None,
         }
     }
 }
 
-impl Default for Var {
+impl Default for VarKind {
     fn default() -> Self {
-        Var::Text {
-            identifier: String::new(),
-            message: None,
-            help_message: None,
+        VarKind::Text {
             placeholder: None,
             initial_value: None,
             default: None,
+            pattern: None,
+            min_length: None,
+            max_length: None,
+            required: default_required(),
+            error: None,
+        }
+    }
+}
+
+fn default_required() -> bool {
+    true
+}
+
+/// The type of a value in the context.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ValueType {
+    String,
+    Bool,
+    Int,
+    Float,
+    /// A list from comma-separated values.
+    List,
+    Json,
+}
+
+impl ValueType {
+    /// Convert a value to this type, parsing it when it's a string.
+    pub(crate) fn convert(self, value: &Value) -> Result<Value, String> {
+        let Value::String(string) = value else {
+            return match (self, value) {
+                (ValueType::String, value) => Ok(value.to_string().into()),
+                (ValueType::Int, Value::Number(number)) if !number.is_f64() => Ok(value.clone()),
+                (ValueType::Bool, Value::Bool(_))
+                | (ValueType::List, Value::Array(_))
+                | (ValueType::Json, _) => Ok(value.clone()),
+                (ValueType::Float, Value::Number(number)) => {
+                    Ok(number.as_f64().map_or_else(|| value.clone(), Value::from))
+                }
+                _ => self.convert(&value.to_string().into()),
+            };
+        };
+
+        match self {
+            ValueType::String => Ok(value.clone()),
+            ValueType::Bool => parse_bool(string)
+                .map(Value::from)
+                .ok_or_else(|| "Expected true or false".to_string()),
+            ValueType::Int => string
+                .trim()
+                .parse::<i64>()
+                .map(Value::from)
+                .map_err(|_| "Expected a whole number".to_string()),
+            ValueType::Float => match string.trim().parse::<f64>() {
+                Ok(number) if number.is_finite() => Ok(number.into()),
+                _ => Err("Expected a number".to_string()),
+            },
+            ValueType::List => Ok(string
+                .split(',')
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .collect::<Vec<_>>()
+                .into()),
+            ValueType::Json => {
+                serde_json::from_str(string).map_err(|e| format!("Expected valid JSON: {e}"))
+            }
         }
     }
 }
+
+/// Parse a boolean from common ways of answering yes or no.
+pub(crate) fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" => Some(true),
+        "false" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Create a value for a number, keeping whole numbers as integers so they
+/// render without a fractional part.
+pub(crate) fn number_value(number: f64) -> Value {
+    if number.fract() == 0.0 && number.abs() < 1e15 {
+        #[allow(clippy::cast_possible_truncation)]
+        Value::from(number as i64)
+    } else {
+        Value::from(number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn strings_are_converted() {
+        assert_eq!(ValueType::String.convert(&json!(5)), Ok(json!("5")));
+        assert_eq!(ValueType::Bool.convert(&json!(" Yes ")), Ok(json!(true)));
+        assert_eq!(ValueType::Int.convert(&json!(" 42 ")), Ok(json!(42)));
+        assert_eq!(ValueType::Float.convert(&json!("2.5")), Ok(json!(2.5)));
+        assert_eq!(
+            ValueType::List.convert(&json!("a, b,,c ")),
+            Ok(json!(["a", "b", "c"]))
+        );
+        assert_eq!(
+            ValueType::Json.convert(&json!(r#"{"a": [1]}"#)),
+            Ok(json!({"a": [1]}))
+        );
+    }
+
+    #[test]
+    fn invalid_strings_are_rejected() {
+        assert!(ValueType::Bool.convert(&json!("maybe")).is_err());
+        assert!(ValueType::Int.convert(&json!("4.2")).is_err());
+        assert!(ValueType::Float.convert(&json!("inf")).is_err());
+        assert!(ValueType::Json.convert(&json!("{")).is_err());
+    }
+
+    #[test]
+    fn other_values_are_converted() {
+        assert_eq!(ValueType::Bool.convert(&json!(false)), Ok(json!(false)));
+        assert_eq!(ValueType::Int.convert(&json!(3)), Ok(json!(3)));
+        assert!(ValueType::Int.convert(&json!(3.5)).is_err());
+        assert_eq!(ValueType::Float.convert(&json!(3)), Ok(json!(3.0)));
+        assert_eq!(ValueType::Bool.convert(&json!(1)), Ok(json!(true)));
+        assert_eq!(ValueType::List.convert(&json!(["a"])), Ok(json!(["a"])));
+        assert_eq!(ValueType::Json.convert(&json!([1, 2])), Ok(json!([1, 2])));
+    }
+
+    fn var(source: &str) -> Var {
+        toml::from_str(&format!("identifier = \"name\"\n{source}")).expect("valid var")
+    }
+
+    #[test]
+    fn multiselect_default_is_optional() {
+        let options = "type = \"multiselect\"\noptions = [\"a\", \"b\"]";
+
+        assert_eq!(var(options).default_value(), None);
+        assert_eq!(
+            var(&format!("{options}\ndefault = [\"b\"]")).default_value(),
+            Some(json!(["b"]))
+        );
+    }
+}
@@ -5,20 +5,27 @@ use serde::{
     ser::{SerializeMap, SerializeSeq},
 };
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::path::Path;
-use steel::{SteelErr, SteelVal, steel_vm::engine::Engine};
-use tera::Context;
+use steel::{SteelErr, SteelVal, rerrs::ErrorKind, rvals::IntoSteelVal, steel_vm::engine::Engine};
+use tera::{Context, Value};
+
+use super::config::VarOption;
 
 const FUNCTION_CWD: &str = "cwd";
 const FUNCTION_INFO: &str = "info";
 const FUNCTION_CONTEXT: &str = "context";
+const FUNCTION_FINALIZE_CONTEXT: &str = "finalize-context";
 const FUNCTION_MESSAGE: &str = "message";
-const FUNCTION_HELP_MESSAGE: &str = "help-message ";
-const FUNCTION_PLACEHOLDER: &str = "placeholder ";
-const FUNCTION_INITIAL_VALUE: &str = "initial-value ";
-const FUNCTION_DEFAULT: &str = "default ";
+const FUNCTION_HELP_MESSAGE: &str = "help-message";
+const FUNCTION_PLACEHOLDER: &str = "placeholder";
+const FUNCTION_INITIAL_VALUE: &str = "initial-value";
+const FUNCTION_DEFAULT: &str = "default";
 const FUNCTION_VALIDATE: &str = "validate";
 const FUNCTION_OPTIONS: &str = "options";
+const FUNCTION_AFTER_WRITE: &str = "after-write";
+const FUNCTION_INCLUDE_FILE: &str = "include-file?";
+const VALUE_CONTEXT: &str = "*context*";
 
 #[derive(Clone)]
 pub(crate) struct Plugins {
@@ -93,26 +100,58 @@ impl Plugins {
         Ok(info)
     }
 
+    /// Make the values collected so far available to every function as
+    /// `*context*`.
+    pub(crate) fn set_context(&self, context: &Context) -> Result<()> {
+        let mut vm = self.vm.borrow_mut();
+        let Some(vm) = vm.as_mut() else {
+            return Ok(());
+        };
+        let context = json_to_steel_val(context.clone().into_json())?;
+
+        vm.update_value(VALUE_CONTEXT, context);
+
+        Ok(())
+    }
+
+    /// Merge the values returned by the `context` function into the context
+    /// before collecting values.
     pub(crate) fn context(&self, context: Context) -> Result<Context> {
-        let arguments = vec![];
-        let Some(result) = self.call_function(FUNCTION_CONTEXT, arguments) else {
+        self.merge_context(FUNCTION_CONTEXT, context)
+    }
+
+    /// Merge the values returned by the `finalize-context` function into the
+    /// context after all values are collected.
+    pub(crate) fn finalize_context(&self, context: Context) -> Result<Context> {
+        self.merge_context(FUNCTION_FINALIZE_CONTEXT, context)
+    }
+
+    fn merge_context(&self, name: &str, mut context: Context) -> Result<Context> {
+        let argument = json_to_steel_val(context.clone().into_json())?;
+        let Some(mut result) = self.call_function(name, vec![argument]) else {
             return Ok(context);
         };
-        let SteelVal::HashMapV(context) = result? else {
+
+        // Support `context` functions that don't take the context.
+        if name == FUNCTION_CONTEXT
+            && matches!(&result, Err(e) if e.kind() == ErrorKind::ArityMismatch)
+            && let Some(retry) = self.call_function(name, vec![])
+        {
+            result = retry;
+        }
+
+        let SteelVal::HashMapV(values) = result? else {
             return Err(anyhow::Error::msg(format!(
-                "Plugin {FUNCTION_CONTEXT:?} should return a hashmap"
+                "Plugin {name:?} should return a hashmap"
             )));
         };
-        let context = context
-            .iter()
-            .fold(Context::new(), |mut context, (key, val)| {
-                let key: SerializableSteelVal = key.into();
-                let val: SerializableSteelVal = val.into();
 
-                context.insert(key, &val);
+        for (key, val) in values.iter() {
+            let key: SerializableSteelVal = key.into();
+            let val: SerializableSteelVal = val.into();
 
-                context
-            });
+            context.insert(key, &val);
+        }
 
         Ok(context)
     }
@@ -219,11 +258,28 @@ impl Plugins {
         Ok(result)
     }
 
-    pub(crate) fn options(&self, identifier: &str, value: &[String]) -> Result<Vec<String>> {
+    /// Map the options for a variable, passing options with a label or hint
+    /// as hashmaps and the others as just their values.
+    pub(crate) fn options(&self, identifier: &str, value: &[VarOption]) -> Result<Vec<VarOption>> {
         let value_argument = value
             .iter()
-            .map(|v| SteelVal::StringV(v.into()))
-            .collect::<Vec<SteelVal>>();
+            .map(|option| {
+                if option.label.is_none() && option.hint.is_none() {
+                    return Ok(SteelVal::StringV(option.value.clone().into()));
+                }
+
+                let mut map = HashMap::from([("value", option.value.clone())]);
+
+                if let Some(label) = &option.label {
+                    map.insert("label", label.clone());
+                }
+                if let Some(hint) = &option.hint {
+                    map.insert("hint", hint.clone());
+                }
+
+                map.into_steelval()
+            })
+            .collect::<Result<Vec<SteelVal>, SteelErr>>()?;
         let value_argument = SteelVal::ListV(value_argument.into());
         let arguments = vec![identifier.to_string().into(), value_argument];
         let Some(result) = self.call_function(FUNCTION_OPTIONS, arguments) else {
@@ -236,14 +292,35 @@ impl Plugins {
         };
         let result = result.into_iter().try_fold(Vec::new(), |mut result, v| {
             let v = match v {
-                SteelVal::StringV(steel_string) => steel_string.to_string(),
-                SteelVal::NumV(int) => int.to_string(),
-                SteelVal::IntV(int) => int.to_string(),
-                SteelVal::CharV(char) => char.to_string(),
+                SteelVal::HashMapV(map) => {
+                    let get = |key: &str| {
+                        map.iter().find_map(|(k, v)| match k {
+                            SteelVal::StringV(k) | SteelVal::SymbolV(k) if k.as_str() == key => {
+                                Some(String::from(SerializableSteelVal::from(v)))
+                            }
+                            _ => None,
+                        })
+                    };
+                    let Some(value) = get("value") else {
+                        return Err(anyhow::Error::msg(format!(
+                            "Options returned by {FUNCTION_OPTIONS:?} should have a value"
+                        )));
+                    };
+
+                    VarOption {
+                        value,
+                        label: get("label"),
+                        hint: get("hint"),
+                    }
+                }
+                SteelVal::StringV(steel_string) => steel_string.to_string().into(),
+                SteelVal::NumV(int) => int.to_string().into(),
+                SteelVal::IntV(int) => int.to_string().into(),
+                SteelVal::CharV(char) => char.to_string().into(),
                 _ => {
-                    return Err(anyhow::Error::msg(
-                        "List returned by {FUNCTION_OPTIONS:?} should only contain string values",
-                    ));
+                    return Err(anyhow::Error::msg(format!(
+                        "List returned by {FUNCTION_OPTIONS:?} should only contain strings or hashmaps"
+                    )));
                 }
             };
 
@@ -254,6 +331,57 @@ impl Plugins {
 
         Ok(result)
     }
+
+    /// Whether to include a file or directory, given its path relative to the
+    /// template.
+    pub(crate) fn include_file(&self, path: &str) -> Result<bool> {
+        let arguments = vec![path.to_string().into()];
+        let Some(result) = self.call_function(FUNCTION_INCLUDE_FILE, arguments) else {
+            return Ok(true);
+        };
+        let SteelVal::BoolV(include) = result? else {
+            return Err(anyhow::Error::msg(format!(
+                "Plugin {FUNCTION_INCLUDE_FILE:?} should return a boolean"
+            )));
+        };
+
+        Ok(include)
+    }
+
+    /// Call the `after-write` function with the paths of the files that were
+    /// created, replaced and skipped, returning a message to show if any.
+    pub(crate) fn after_write(
+        &self,
+        created: &[String],
+        replaced: &[String],
+        skipped: &[String],
+        context: &Context,
+    ) -> Result<Option<String>> {
+        let paths = |paths: &[String]| {
+            SteelVal::ListV(
+                paths
+                    .iter()
+                    .map(|path| SteelVal::StringV(path.clone().into()))
+                    .collect::<Vec<_>>()
+                    .into(),
+            )
+        };
+        let arguments = vec![
+            paths(created),
+            paths(replaced),
+            paths(skipped),
+            json_to_steel_val(context.clone().into_json())?,
+        ];
+        let Some(result) = self.call_function(FUNCTION_AFTER_WRITE, arguments) else {
+            return Ok(None);
+        };
+        let message = match result? {
+            SteelVal::StringV(message) if !message.is_empty() => Some(message.to_string()),
+            _ => None,
+        };
+
+        Ok(message)
+    }
 }
 
 impl TryFrom<String> for Plugins {
@@ -262,6 +390,10 @@ impl TryFrom<String> for Plugins {
     fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
         let mut vm = steel::steel_vm::engine::Engine::new();
 
+        vm.register_value(
+            VALUE_CONTEXT,
+            HashMap::<String, SteelVal>::new().into_steelval()?,
+        );
         vm.run(value)?;
 
         let vm = RefCell::new(Some(vm));
@@ -270,6 +402,32 @@ impl TryFrom<String> for Plugins {
     }
 }
 
+fn json_to_steel_val(value: Value) -> Result<SteelVal, SteelErr> {
+    let value = match value {
+        Value::Null => SteelVal::Void,
+        Value::Bool(bool) => SteelVal::BoolV(bool),
+        Value::Number(number) => match number.as_i64().and_then(|int| isize::try_from(int).ok()) {
+            Some(int) => SteelVal::IntV(int),
+            None => SteelVal::NumV(number.as_f64().unwrap_or_default()),
+        },
+        Value::String(string) => SteelVal::StringV(string.into()),
+        Value::Array(values) => SteelVal::ListV(
+            values
+                .into_iter()
+                .map(json_to_steel_val)
+                .collect::<Result<Vec<_>, _>>()?
+                .into(),
+        ),
+        Value::Object(map) => map
+            .into_iter()
+            .map(|(key, value)| Ok((key, json_to_steel_val(value)?)))
+            .collect::<Result<HashMap<_, _>, SteelErr>>()?
+            .into_steelval()?,
+    };
+
+    Ok(value)
+}
+
 struct SerializableSteelVal<'a> {
     steel_val: &'a SteelVal,
 }
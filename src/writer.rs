@@ -11,6 +11,15 @@ use crate::processor::{
     actions::{Action, Write},
 };
 
+/// The files that were written or skipped, relative to the current directory.
+#[derive(Default)]
+pub(crate) struct WriteReport {
+    pub(crate) created: Vec<String>,
+    pub(crate) replaced: Vec<String>,
+    /// Existing files that weren't replaced.
+    pub(crate) skipped: Vec<String>,
+}
+
 pub(crate) struct Writer<'a> {
     process_result: &'a ProcessResult,
 }
@@ -20,19 +29,26 @@ impl<'a> Writer<'a> {
         Writer { process_result }
     }
 
-    pub(crate) fn write(&self) -> Result<()> {
+    pub(crate) fn write(&self) -> Result<WriteReport> {
         let tera = &self.process_result.tera;
         let context = &self.process_result.context;
         let mut replace_all = false;
+        let mut report = WriteReport::default();
 
         for action in &self.process_result.actions {
             let (name, target) = match action {
-                Action::Create(write) => (&write.name, &write.target),
+                Action::Create(write) => {
+                    report.created.push(write.name.clone());
+
+                    (&write.name, &write.target)
+                }
                 Action::Replace(write) => {
                     if !replace_all {
                         let prompt_result = self.prompt(write)?;
 
                         if let PromptResult::No = prompt_result {
+                            report.skipped.push(write.name.clone());
+
                             continue;
                         }
 
@@ -41,6 +57,8 @@ impl<'a> Writer<'a> {
                         }
                     }
 
+                    report.replaced.push(write.name.clone());
+
                     (&write.name, &write.target)
                 }
             };
@@ -56,6 +74,34 @@ impl<'a> Writer<'a> {
             tera.render_to(name, context, file)?;
         }
 
+        Ok(report)
+    }
+
+    /// Render every file without writing it, showing the size of new files
+    /// and the differences for files that would be replaced.
+    pub(crate) fn preview(&self) -> Result<()> {
+        let tera = &self.process_result.tera;
+        let context = &self.process_result.context;
+        let mut sizes = Vec::new();
+
+        for action in &self.process_result.actions {
+            match action {
+                Action::Create(write) => {
+                    let contents = tera.render(&write.name, context)?;
+
+                    sizes.push(format!("- {} ({} bytes)", write.name, contents.len()));
+                }
+                Action::Replace(write) => diff::diff(tera, context, write)?,
+            }
+        }
+
+        if !sizes.is_empty() {
+            cliclack::log::info(format!(
+                "Would create the following files:\n{}",
+                sizes.join("\n")
+            ))?;
+        }
+
         Ok(())
     }
 